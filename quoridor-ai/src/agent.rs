@@ -0,0 +1,191 @@
+//! One interface for every bot, so front ends can drive any of them the same
+//! way: hand it a game, tell it the moves other players make and ask it for
+//! its own.
+
+use quoridor_game::{Board, GameRecord, IllegalMove, Move};
+use std::{fmt, time::Duration};
+
+/// How much thinking an agent may do for one move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Budget {
+    /// Whatever the agent was set up with.
+    Default,
+    Time(Duration),
+    /// Positions searched, or playouts for MCTS.
+    Nodes(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentError {
+    /// A move doesn't fit the agent's copy of the game.
+    IllegalMove(Move, IllegalMove),
+    /// There was nothing to play, for instance because the game is over.
+    NoMove,
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::IllegalMove(mov, e) => write!(f, "{} is illegal: {}", mov, e),
+            AgentError::NoMove => write!(f, "no move to play"),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+/// A bot that keeps its own copy of the game it is playing.
+pub trait Agent<B: Board + Clone> {
+    /// Starts playing `game`, dropping anything learned about the last one.
+    fn new_game(&mut self, game: GameRecord<B>);
+
+    /// Plays a move someone else made.
+    fn observe(&mut self, mov: &Move) -> Result<(), AgentError>;
+
+    /// Picks a move for the side to move within `budget` and plays it.
+    fn choose(&mut self, budget: Budget) -> Result<Move, AgentError>;
+
+    fn game(&self) -> &GameRecord<B>;
+
+    /// Goes back to the start of the current game.
+    fn reset(&mut self) {
+        let game = self.game();
+        let start = GameRecord::from_position(game.start().clone(), game.start_player());
+        self.new_game(start);
+    }
+}
+
+/// Plays `mov` in `game` if it is legal, for agents to share.
+#[cfg(any(
+    feature = "use-greedy",
+    feature = "use-rubot",
+    feature = "use-mcts",
+    feature = "use-alphabeta"
+))]
+pub(crate) fn apply<B: Board + Clone>(
+    game: &mut GameRecord<B>,
+    mov: &Move,
+) -> Result<(), AgentError> {
+    game.play(mov)
+        .map_err(|e| AgentError::IllegalMove(mov.clone(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quoridor_game::walls_per_player;
+    #[allow(unused_imports)]
+    use quoridor_game::{bitpacked::BoardV2, v1::BoardV1};
+
+    /// Drives `agent` through the calls a front end makes on a two player
+    /// board of `size`.
+    #[allow(dead_code)]
+    fn check_agent<B: Board + Clone>(mut agent: impl Agent<B>, size: u8) {
+        let position = |pawns: &str, side| {
+            let walls = walls_per_player(size, 2);
+            let fen = format!("- - {} {},{} {} {}", pawns, walls, walls, side, size);
+            GameRecord::<B>::from_fen(&fen).unwrap()
+        };
+        let file = (b'a' + size / 2) as char;
+        let game = position(&format!("{}2,{}{}", file, file, size - 1), 1);
+        agent.new_game(game.clone());
+        assert_eq!(game.fen(), agent.game().fen());
+
+        let teleport = Move::MoveTo(size / 2, 5);
+        assert!(matches!(
+            agent.observe(&teleport),
+            Err(AgentError::IllegalMove(mov, _)) if mov == teleport
+        ));
+        assert_eq!(game.fen(), agent.game().fen());
+
+        let step = Move::MoveTo(size / 2, 2);
+        agent.observe(&step).unwrap();
+        let mov = agent.choose(Budget::Nodes(50)).unwrap();
+        let mut expected = game.clone();
+        expected.play(&step).unwrap();
+        expected.play(&mov).unwrap();
+        assert_eq!(expected.fen(), agent.game().fen());
+
+        agent.reset();
+        assert_eq!(game.fen(), agent.game().fen());
+        assert_eq!(0, agent.game().ply());
+
+        agent.new_game(position(
+            &format!("{}{},{}{}", file, size, file, size - 1),
+            2,
+        ));
+        assert_eq!(Err(AgentError::NoMove), agent.choose(Budget::Nodes(50)));
+    }
+
+    #[test]
+    #[cfg(feature = "use-greedy")]
+    fn greedy_is_an_agent() {
+        use crate::greedy::GreedyAiPlayer;
+        check_agent(
+            GreedyAiPlayer::with_game(GameRecord::new(BoardV2::empty())),
+            9,
+        );
+        check_agent(
+            GreedyAiPlayer::with_game(GameRecord::new(BoardV1::empty())),
+            11,
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "use-rubot")]
+    fn rubot_is_an_agent() {
+        use crate::rubot::RubotAiPlayer;
+        let game = GameRecord::new(BoardV2::empty());
+        check_agent(RubotAiPlayer::new(game, Budget::Default), 9);
+        let game = GameRecord::new(BoardV1::empty());
+        check_agent(RubotAiPlayer::new(game, Budget::Default), 11);
+    }
+
+    #[test]
+    #[cfg(feature = "use-mcts")]
+    fn mcts_is_an_agent() {
+        use crate::mcts::MctsAiPlayer;
+        check_agent(
+            MctsAiPlayer::with_game(GameRecord::new(BoardV2::empty()), 1000),
+            9,
+        );
+        check_agent(
+            MctsAiPlayer::with_game(GameRecord::new(BoardV1::empty()), 1000),
+            11,
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "use-alphabeta")]
+    fn alpha_beta_is_an_agent() {
+        use crate::alphabeta::AlphaBetaAiPlayer;
+        let game = GameRecord::new(BoardV2::empty());
+        check_agent(AlphaBetaAiPlayer::new(game, Budget::Default), 9);
+        let game = GameRecord::new(BoardV1::empty());
+        check_agent(AlphaBetaAiPlayer::new(game, Budget::Default), 11);
+    }
+
+    /// Two agents finish a game on a board only `BoardV1` can hold.
+    #[test]
+    #[cfg(all(feature = "use-greedy", feature = "use-alphabeta"))]
+    fn plays_an_11x11_game() {
+        let game = GameRecord::new(BoardV1::with_size(11).unwrap());
+        let mut agents: [Box<dyn Agent<BoardV1>>; 2] = [
+            Box::new(crate::greedy::GreedyAiPlayer::with_game(game.clone())),
+            Box::new(crate::alphabeta::AlphaBetaAiPlayer::new(
+                game,
+                Budget::Nodes(200),
+            )),
+        ];
+        for turn in 0..400 {
+            if agents[0].game().winner().is_some() {
+                break;
+            }
+            let mov = agents[turn % 2].choose(Budget::Default).unwrap();
+            agents[(turn + 1) % 2].observe(&mov).unwrap();
+        }
+        let winner = agents[0].game().winner().expect("someone wins");
+        assert_eq!(Some(winner), agents[1].game().winner());
+        assert_eq!(agents[0].game().fen(), agents[1].game().fen());
+    }
+}
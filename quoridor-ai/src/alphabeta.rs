@@ -0,0 +1,398 @@
+//! Negamax alpha-beta search: iterative deepening with a transposition
+//! table keyed by `Board::position_key`, principal variation search, and
+//! killer and history heuristics to order moves. With a node budget it plays
+//! the same move every time, which makes it a baseline for the other bots.
+//!
+//! The evaluation is the race margin from `Board::race_margin` plus a little
+//! for walls in hand. With more than two players everyone else is treated as
+//! one opponent, which negamax can't search soundly, so it is only meant for
+//! two player games.
+
+use crate::agent::{self, Agent, AgentError, Budget};
+use quoridor_game::{
+    bitpacked::BoardV2, Board, GameRecord, Move, MoveList, Orientation, Player, MAX_SIZE,
+};
+use std::{
+    cmp::Reverse,
+    time::{Duration, Instant},
+};
+
+const MAX_PLY: usize = 64;
+const INFINITY: i16 = 30_000;
+/// Winning at ply `n` of the search scores `WIN - n`.
+const WIN: i16 = 20_000;
+/// One move of race margin is worth this many walls in hand.
+const MARGIN_WEIGHT: i16 = 4;
+const TABLE_SIZE: usize = 1 << 18;
+/// Squares of the largest board, then each wall location in both
+/// orientations.
+const MOVE_INDICES: usize = SQUARES + WALL_SLOTS * 2;
+const SQUARES: usize = MAX_SIZE as usize * MAX_SIZE as usize;
+const WALL_SLOTS: usize = (MAX_SIZE as usize - 1) * (MAX_SIZE as usize - 1);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    /// The score is at least this.
+    Lower,
+    /// The score is at most this.
+    Upper,
+}
+
+#[derive(Clone)]
+struct Entry {
+    key: u64,
+    depth: u8,
+    score: i16,
+    bound: Bound,
+    mov: Option<Move>,
+}
+
+/// What a finished search found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub mov: Move,
+    /// From the point of view of the side to move.
+    pub score: i16,
+    /// The deepest iteration that finished.
+    pub depth: u8,
+    pub nodes: u64,
+}
+
+pub struct AlphaBetaAiPlayer<B = BoardV2> {
+    game: GameRecord<B>,
+    budget: Budget,
+    table: Vec<Option<Entry>>,
+    killers: Vec<[Option<Move>; 2]>,
+    history: [u32; MOVE_INDICES],
+    nodes: u64,
+    deadline: Option<Instant>,
+    max_nodes: Option<u64>,
+    aborted: bool,
+    /// Whether an iteration of the current search has finished.
+    finished: bool,
+    root_best: Option<(Move, i16)>,
+}
+
+impl<B: Board + Clone> AlphaBetaAiPlayer<B> {
+    /// `budget` is what `Budget::Default` stands for.
+    pub fn new(game: GameRecord<B>, budget: Budget) -> Self {
+        Self {
+            game,
+            budget,
+            table: vec![None; TABLE_SIZE],
+            killers: vec![[None, None]; MAX_PLY],
+            history: [0; MOVE_INDICES],
+            nodes: 0,
+            deadline: None,
+            max_nodes: None,
+            aborted: false,
+            finished: false,
+            root_best: None,
+        }
+    }
+
+    /// Searches the current position without playing the move found. At
+    /// least the first iteration always finishes, so there is a move unless
+    /// the game is over.
+    pub fn search(&mut self, budget: Budget) -> Option<SearchResult> {
+        let budget = match budget {
+            Budget::Default => self.budget,
+            budget => budget,
+        };
+        let (deadline, max_nodes) = match budget {
+            Budget::Time(time) => (Some(Instant::now() + time), None),
+            Budget::Nodes(nodes) => (None, Some(nodes)),
+            Budget::Default => (Some(Instant::now() + Duration::from_secs(1)), None),
+        };
+        self.deadline = deadline;
+        self.max_nodes = max_nodes;
+        self.nodes = 0;
+        self.aborted = false;
+        self.finished = false;
+        self.root_best = None;
+        self.killers = vec![[None, None]; MAX_PLY];
+        for score in self.history.iter_mut() {
+            *score /= 2;
+        }
+
+        let mut result = None;
+        for depth in 1..MAX_PLY as u8 {
+            let score = self.negamax(depth, 0, -INFINITY, INFINITY);
+            if let Some((mov, best)) = self.root_best.clone() {
+                result = Some(SearchResult {
+                    mov,
+                    score: if self.aborted { best } else { score },
+                    depth: if self.aborted { depth - 1 } else { depth },
+                    nodes: self.nodes,
+                });
+            }
+            if self.aborted || result.is_none() || score.abs() >= WIN - MAX_PLY as i16 {
+                break;
+            }
+            self.finished = true;
+        }
+        result
+    }
+
+    fn out_of_budget(&self) -> bool {
+        // the first iteration always finishes
+        if !self.finished {
+            return false;
+        }
+        if let Some(max_nodes) = self.max_nodes {
+            if self.nodes >= max_nodes {
+                return true;
+            }
+        }
+        match self.deadline {
+            Some(deadline) => self.nodes & 255 == 0 && Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    fn negamax(&mut self, depth: u8, ply: usize, mut alpha: i16, beta: i16) -> i16 {
+        self.nodes += 1;
+        if self.out_of_budget() {
+            self.aborted = true;
+            return 0;
+        }
+        // whoever just moved won
+        if self.game.winner().is_some() {
+            return -(WIN - ply as i16);
+        }
+        if depth == 0 || ply + 1 >= MAX_PLY {
+            return self.evaluate();
+        }
+
+        let key = self.game.position_key();
+        let slot = key as usize % TABLE_SIZE;
+        let mut table_move = None;
+        if let Some(entry) = &self.table[slot] {
+            if entry.key == key {
+                table_move = entry.mov.clone();
+                let score = from_table(entry.score, ply);
+                if ply > 0 && entry.depth >= depth {
+                    match entry.bound {
+                        Bound::Exact => return score,
+                        Bound::Lower if score >= beta => return score,
+                        Bound::Upper if score <= alpha => return score,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let moves = self.ordered_moves(ply, table_move.as_ref());
+        if moves.is_empty() {
+            return self.evaluate();
+        }
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        for (i, mov) in moves.into_iter().enumerate() {
+            self.game
+                .apply_move(&mov)
+                .expect("generated moves are legal");
+            let mut score = if i == 0 {
+                -self.negamax(depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                -self.negamax(depth - 1, ply + 1, -alpha - 1, -alpha)
+            };
+            if i > 0 && score > alpha && score < beta && !self.aborted {
+                score = -self.negamax(depth - 1, ply + 1, -beta, -alpha);
+            }
+            self.game.undo();
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(mov.clone());
+                if ply == 0 {
+                    self.root_best = Some((mov.clone(), score));
+                }
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                let killers = &mut self.killers[ply];
+                if killers[0].as_ref() != Some(&mov) {
+                    killers[1] = killers[0].take();
+                    killers[0] = Some(mov.clone());
+                }
+                self.history[move_index(&mov)] += u32::from(depth) * u32::from(depth);
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table[slot] = Some(Entry {
+            key,
+            depth,
+            score: to_table(best, ply),
+            bound,
+            mov: best_move,
+        });
+        best
+    }
+
+    /// The legal moves, best guesses first: the table move, then killers,
+    /// then pawn moves, then by history.
+    fn ordered_moves(&self, ply: usize, table_move: Option<&Move>) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        self.game.generate_moves(&mut moves);
+        let killers = &self.killers[ply];
+        let mut scored = moves
+            .into_iter()
+            .map(|mov| {
+                let score = if Some(&mov) == table_move {
+                    u32::MAX
+                } else if killers[0].as_ref() == Some(&mov) {
+                    u32::MAX - 1
+                } else if killers[1].as_ref() == Some(&mov) {
+                    u32::MAX - 2
+                } else if let Move::MoveTo(..) = mov {
+                    u32::MAX - 3
+                } else {
+                    self.history[move_index(&mov)].min(u32::MAX - 4)
+                };
+                (score, mov)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+        scored.into_iter().map(|(_, mov)| mov).collect()
+    }
+
+    /// The position from the side to move's point of view.
+    fn evaluate(&self) -> i16 {
+        let board = self.game.board();
+        let player = self.game.current_player();
+        let margin = board.race_margin(player, player).unwrap_or(0);
+        let walls = |p: Player| i16::from(board.available_walls(p));
+        let rival_walls = board
+            .players()
+            .iter()
+            .filter(|p| **p != player)
+            .map(|p| walls(*p))
+            .max()
+            .unwrap_or(0);
+        margin * MARGIN_WEIGHT + walls(player) - rival_walls
+    }
+}
+
+/// Wins are stored as distances from the node rather than the root.
+fn to_table(score: i16, ply: usize) -> i16 {
+    if score >= WIN - MAX_PLY as i16 {
+        score + ply as i16
+    } else if score <= -WIN + MAX_PLY as i16 {
+        score - ply as i16
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i16, ply: usize) -> i16 {
+    if score >= WIN - MAX_PLY as i16 {
+        score - ply as i16
+    } else if score <= -WIN + MAX_PLY as i16 {
+        score + ply as i16
+    } else {
+        score
+    }
+}
+
+fn move_index(mov: &Move) -> usize {
+    match *mov {
+        Move::MoveTo(x, y) => y as usize * MAX_SIZE as usize + x as usize,
+        Move::AddWall {
+            location: (x, y),
+            orientation,
+        } => {
+            let orientation = match orientation {
+                Orientation::Horizontal => 0,
+                Orientation::Vertical => 1,
+            };
+            let slot = y as usize * (MAX_SIZE as usize - 1) + x as usize;
+            SQUARES + slot * 2 + orientation
+        }
+    }
+}
+
+impl<B: Board + Clone> Agent<B> for AlphaBetaAiPlayer<B> {
+    fn new_game(&mut self, game: GameRecord<B>) {
+        *self = Self::new(game, self.budget);
+    }
+
+    fn observe(&mut self, mov: &Move) -> Result<(), AgentError> {
+        agent::apply(&mut self.game, mov)
+    }
+
+    fn choose(&mut self, budget: Budget) -> Result<Move, AgentError> {
+        let mov = self.search(budget).ok_or(AgentError::NoMove)?.mov;
+        agent::apply(&mut self.game, &mov)?;
+        Ok(mov)
+    }
+
+    fn game(&self) -> &GameRecord<B> {
+        &self.game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, nodes: u64) -> SearchResult {
+        let game = GameRecord::<BoardV2>::from_fen(fen).unwrap();
+        AlphaBetaAiPlayer::new(game, Budget::Default)
+            .search(Budget::Nodes(nodes))
+            .unwrap()
+    }
+
+    #[test]
+    fn finds_wins_and_stops_losses() {
+        let result = search("- - e8,a5 0,0 1", 1_000);
+        assert_eq!(Move::MoveTo(4, 8), result.mov);
+        assert_eq!(WIN - 1, result.score);
+
+        // player 2 walks in next move unless player 1 walls it into its box
+        let result = search("- a2 e7,a2 1,0 1", 20_000);
+        assert_eq!(
+            Move::AddWall {
+                orientation: Orientation::Horizontal,
+                location: (0, 0)
+            },
+            result.mov
+        );
+        assert!(result.score > 0);
+    }
+
+    #[test]
+    fn node_budgets_are_deterministic() {
+        let first = search("- - e1,e9 10,10 1", 5_000);
+        assert_eq!(first, search("- - e1,e9 10,10 1", 5_000));
+        assert!(first.depth >= 2);
+
+        let game = GameRecord::<BoardV2>::from_fen("- - e9,e8 10,10 2").unwrap();
+        let mut player = AlphaBetaAiPlayer::new(game, Budget::Nodes(100));
+        assert_eq!(None, player.search(Budget::Default));
+    }
+
+    #[test]
+    fn finishes_the_first_iteration() {
+        let result = search("- - e1,e9 10,10 1", 1);
+        assert_eq!(1, result.depth);
+        assert!(result.nodes > 131);
+        assert_eq!(Move::MoveTo(4, 1), result.mov);
+    }
+}
@@ -1,55 +1,71 @@
-use quoridor_game::{Board, Move, Player};
+use crate::agent::{self, Agent, AgentError, Budget};
+use quoridor_game::{Board, GameRecord, Move, Player};
 use std::hash::Hash;
 
 pub struct GreedyAiPlayer<B: Board + Clone> {
-    board: B,
-    current_player: Player,
+    game: GameRecord<B>,
 }
 
 impl<B: Board + Clone + Hash + Eq> GreedyAiPlayer<B> {
     pub fn new(board: B, current_player: Player) -> Self {
-        Self {
-            board,
-            current_player: current_player,
-        }
+        Self::with_game(GameRecord::from_position(board, current_player))
+    }
+
+    pub fn with_game(game: GameRecord<B>) -> Self {
+        Self { game }
+    }
+
+    pub fn current_player(&self) -> Player {
+        self.game.current_player()
     }
 }
 
-impl<B: Board + Clone + Hash + Eq> GreedyAiPlayer<B> {
-    pub fn send(&mut self, m: &Move) -> Result<(), ()> {
-        self.board.apply_move(m, self.current_player)?;
-        self.current_player = self.current_player.other();
-        Ok(())
+/// Looks one move ahead, so the budget makes no difference.
+impl<B: Board + Clone + Hash + Eq> Agent<B> for GreedyAiPlayer<B> {
+    fn new_game(&mut self, game: GameRecord<B>) {
+        self.game = game;
+    }
+
+    fn observe(&mut self, mov: &Move) -> Result<(), AgentError> {
+        agent::apply(&mut self.game, mov)
     }
 
-    pub fn receive(&mut self) -> Result<Move, ()> {
-        let m = best_move(self.board.clone(), self.current_player)?;
-        self.board.apply_move(&m, self.current_player)?;
-        self.current_player = self.current_player.other();
+    fn choose(&mut self, _: Budget) -> Result<Move, AgentError> {
+        if self.game.winner().is_some() {
+            return Err(AgentError::NoMove);
+        }
+        let m = best_move(self.game.board().clone(), self.game.current_player())
+            .map_err(|_| AgentError::NoMove)?;
+        agent::apply(&mut self.game, &m)?;
         Ok(m)
     }
+
+    fn game(&self) -> &GameRecord<B> {
+        &self.game
+    }
 }
 
-pub fn best_move<B: Board + Clone + Hash + Eq>(board: B, player: Player) -> Result<Move, ()> {
+pub fn best_move<B: Board + Clone + Hash + Eq>(mut board: B, player: Player) -> Result<Move, ()> {
     let legal_moves = board.legal_moves(player);
-    let boards = legal_moves.into_iter().filter_map(|mov| {
-        let mut nb = board.clone();
-        nb.apply_move(&mov, player).ok()?;
-        Some((mov, nb))
-    });
-
-    let distances = boards.map(|(mov, board)| {
-        (
-            mov,
-            board.distance_to_goal(player).unwrap() as i8,
-            board.distance_to_goal(player.other()).unwrap() as i8,
-        )
+    let scores = legal_moves.into_iter().filter_map(|mov| {
+        let applied = board.make_move(&mov, player).ok()?;
+        let margin = board.race_margin(player, player.next(board.num_players()));
+        board.undo_move(&applied);
+        Some((mov, margin?))
     });
 
-    let scores = distances.map(|(mov, my_dist, their_dist)| (mov, their_dist - my_dist));
-
     scores
         .max_by_key(|(_, score)| *score)
         .map(|x| x.0)
         .ok_or(())
 }
+
+/// Distance to goal of whichever opponent is nearest to winning.
+pub fn closest_opponent_distance<B: Board>(board: &B, player: Player) -> Option<u8> {
+    board
+        .players()
+        .iter()
+        .filter(|p| **p != player)
+        .map(|p| board.distance_to_goal(*p))
+        .try_fold(u8::MAX, |closest, d| d.map(|d| closest.min(d)))
+}
@@ -1,6 +1,11 @@
+pub mod agent;
+#[cfg(feature = "use-alphabeta")]
+pub mod alphabeta;
 #[cfg(feature = "use-greedy")]
 pub mod greedy;
 #[cfg(feature = "use-mcts")]
 pub mod mcts;
 #[cfg(feature = "use-rubot")]
 pub mod rubot;
+
+pub use agent::{Agent, AgentError, Budget};
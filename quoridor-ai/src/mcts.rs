@@ -1,112 +1,455 @@
-use fxhash::FxHasher;
+use crate::agent::{self, Agent, AgentError, Budget};
 use mcts::transposition_table::*;
 use mcts::tree_policy::*;
 use mcts::*;
-use quoridor_game::{bitpacked::BoardV2, Board, Move, Player};
+use quoridor_game::{bitpacked::BoardV2, Board, GameRecord, Move, Player};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 use std::{
-    fmt::Debug,
-    hash::{Hash, Hasher},
+    collections::{hash_map::Entry, HashMap},
+    fmt::{self, Debug},
+    hash::Hash,
     marker::PhantomData,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use super::greedy;
 
 #[derive(Clone, Debug)]
 pub enum QuoridorState<B: Board + Clone> {
-    Dirty { offender: Player },
-    Clean { board: B, current_player: Player },
+    Dirty { offender: Player, players: u8 },
+    Clean { game: GameRecord<B> },
 }
 
-pub struct MctsAiPlayer {
-    state: QuoridorState<BoardV2>,
-    mcts: MCTSManager<QuoridorSpec<BoardV2>>,
-    think_time: u32,
+/// A board the search can share between its threads.
+pub trait SearchBoard: Board + Clone + Hash + Eq + Debug + Send + Sync + 'static {}
+
+impl<B: Board + Clone + Hash + Eq + Debug + Send + Sync + 'static> SearchBoard for B {}
+
+pub struct MctsAiPlayer<B: SearchBoard = BoardV2> {
+    state: QuoridorState<B>,
+    mcts: MCTSManager<QuoridorSpec<B>>,
+    config: MctsConfig,
+    priors: Arc<Priors>,
+    /// How many moves into the game the root of `mcts` is.
+    root_ply: usize,
+}
+
+/// How long one search runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MctsBudget {
+    Playouts(u32),
+    /// Search for this long.
+    Deadline(Duration),
+    /// Search until the tree has this many nodes.
+    Nodes(usize),
+}
+
+/// How a new leaf of the tree is scored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rollout {
+    /// The race margin of each player, without playing on.
+    Static,
+    /// Random moves until someone wins.
+    Random,
+    /// Mostly `greedy::best_move`, with some random moves mixed in.
+    Greedy,
+    /// Random moves until the walls run out, then the race decides.
+    Race,
+    /// The average of a greedy rollout and the static score.
+    Mixed,
+}
+
+impl FromStr for Rollout {
+    type Err = ParseConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "static" => Rollout::Static,
+            "random" => Rollout::Random,
+            "greedy" => Rollout::Greedy,
+            "race" => Rollout::Race,
+            "mixed" => Rollout::Mixed,
+            _ => return Err(ParseConfigError(s.to_string())),
+        })
+    }
+}
+
+impl fmt::Display for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rollout::Static => "static",
+            Rollout::Random => "random",
+            Rollout::Greedy => "greedy",
+            Rollout::Race => "race",
+            Rollout::Mixed => "mixed",
+        })
+    }
+}
+
+/// Settings for `MctsAiPlayer`. Reads and writes as the budget followed by
+/// any settings that differ from the default, such as `10000`, `500ms`,
+/// `2s,threads=2` or `50000nodes,exploration=0.5,table=8192,rollout=race`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsConfig {
+    pub threads: usize,
+    /// The UCT exploration constant.
+    pub exploration: f64,
+    /// Entries in the transposition table.
+    pub table_size: usize,
+    pub budget: MctsBudget,
+    pub rollout: Rollout,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            threads: 16,
+            exploration: 0.2,
+            table_size: 4096,
+            budget: MctsBudget::Playouts(10000),
+            rollout: Rollout::Static,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseConfigError(String);
+
+impl fmt::Display for ParseConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can't read MCTS setting {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseConfigError {}
+
+impl FromStr for MctsBudget {
+    type Err = ParseConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn number<T: FromStr>(digits: &str, s: &str) -> Result<T, ParseConfigError> {
+            digits.parse().map_err(|_| ParseConfigError(s.to_string()))
+        }
+        Ok(if let Some(millis) = s.strip_suffix("ms") {
+            MctsBudget::Deadline(Duration::from_millis(number(millis, s)?))
+        } else if let Some(nodes) = s.strip_suffix("nodes") {
+            MctsBudget::Nodes(number(nodes, s)?)
+        } else if let Some(secs) = s.strip_suffix('s') {
+            MctsBudget::Deadline(Duration::from_secs(number(secs, s)?))
+        } else {
+            MctsBudget::Playouts(number(s, s)?)
+        })
+    }
+}
+
+impl fmt::Display for MctsBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MctsBudget::Playouts(playouts) => write!(f, "{}", playouts),
+            MctsBudget::Deadline(time) if time.subsec_millis() == 0 && time.as_secs() > 0 => {
+                write!(f, "{}s", time.as_secs())
+            }
+            MctsBudget::Deadline(time) => write!(f, "{}ms", time.as_millis()),
+            MctsBudget::Nodes(nodes) => write!(f, "{}nodes", nodes),
+        }
+    }
+}
+
+impl FromStr for MctsConfig {
+    type Err = ParseConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = s.split(',');
+        let mut config = MctsConfig {
+            budget: settings.next().unwrap_or_default().parse()?,
+            ..MctsConfig::default()
+        };
+        for setting in settings {
+            let bad = || ParseConfigError(setting.to_string());
+            let (key, value) = setting.split_once('=').ok_or_else(bad)?;
+            match key {
+                "threads" => config.threads = value.parse().map_err(|_| bad())?,
+                "exploration" => config.exploration = value.parse().map_err(|_| bad())?,
+                "table" => config.table_size = value.parse().map_err(|_| bad())?,
+                "rollout" => config.rollout = value.parse()?,
+                _ => return Err(bad()),
+            }
+        }
+        let no_budget = match config.budget {
+            MctsBudget::Playouts(playouts) => playouts == 0,
+            MctsBudget::Deadline(time) => time == Duration::default(),
+            MctsBudget::Nodes(nodes) => nodes == 0,
+        };
+        if no_budget
+            || config.threads == 0
+            || !config.exploration.is_finite()
+            || config.exploration <= 0.0
+            || config.table_size == 0
+        {
+            return Err(ParseConfigError(s.to_string()));
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for MctsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let default = MctsConfig::default();
+        write!(f, "{}", self.budget)?;
+        if self.threads != default.threads {
+            write!(f, ",threads={}", self.threads)?;
+        }
+        if self.exploration != default.exploration {
+            write!(f, ",exploration={}", self.exploration)?;
+        }
+        if self.table_size != default.table_size {
+            write!(f, ",table={}", self.table_size)?;
+        }
+        if self.rollout != default.rollout {
+            write!(f, ",rollout={}", self.rollout)?;
+        }
+        Ok(())
+    }
+}
+
+/// Visits and rewards from the last search for the position a move leads
+/// to, so a new tree starts where the last one left off.
+#[derive(Clone, Copy, Debug, Default)]
+struct Prior {
+    visits: u64,
+    sum_rewards: i64,
+}
+
+/// Priors by the Zobrist key of the position after the move.
+type Priors = HashMap<u64, Prior>;
+
+/// UCT counting each move's prior as playouts made in this tree.
+struct WarmUctPolicy(f64);
+
+impl<B: SearchBoard> TreePolicy<QuoridorSpec<B>> for WarmUctPolicy {
+    type MoveEvaluation = Prior;
+    type ThreadLocalData = PolicyRng;
+
+    fn choose_child<'a, MoveIter>(
+        &self,
+        moves: MoveIter,
+        mut handle: SearchHandle<QuoridorSpec<B>>,
+    ) -> &'a MoveInfo<QuoridorSpec<B>>
+    where
+        MoveIter: Iterator<Item = &'a MoveInfo<QuoridorSpec<B>>> + Clone,
+    {
+        let total_visits = moves.clone().map(visits).sum::<u64>();
+        let ln_adjusted_total = ((total_visits + 1) as f64).ln();
+        handle
+            .thread_data()
+            .policy_data
+            .select_by_key(moves, |mov| {
+                let child_visits = visits(mov);
+                if child_visits == 0 {
+                    f64::INFINITY
+                } else {
+                    let sum_rewards = mov.sum_rewards() + mov.move_evaluation().sum_rewards;
+                    let explore_term = 2.0 * (ln_adjusted_total / child_visits as f64).sqrt();
+                    self.0 * explore_term + sum_rewards as f64 / child_visits as f64
+                }
+            })
+            .unwrap()
+    }
+}
+
+fn visits<B: SearchBoard>(mov: &MoveInfo<QuoridorSpec<B>>) -> u64 {
+    mov.visits() + mov.move_evaluation().visits
+}
+
+struct QuoridorEvaluator {
+    priors: Arc<Priors>,
+    rollout: Rollout,
+}
+
+/// Rollouts that haven't finished after this many moves get the static score.
+const MAX_ROLLOUT: usize = 200;
+/// How often greedy rollouts play a random move instead.
+const GREEDY_EPSILON: f64 = 0.25;
+
+/// Each search thread gets its own random numbers.
+pub struct RolloutRng(SmallRng);
+
+impl Default for RolloutRng {
+    fn default() -> Self {
+        static SEED: AtomicU64 = AtomicU64::new(0);
+        RolloutRng(SmallRng::seed_from_u64(
+            SEED.fetch_add(1, Ordering::Relaxed),
+        ))
+    }
+}
+
+/// Each player's race margin with the side to move moving first.
+fn static_scores<B: Board + Clone>(game: &GameRecord<B>) -> [i8; 4] {
+    let mut scores = [0; 4];
+    let board = game.board();
+    for player in board.players() {
+        scores[player.index()] = board
+            .race_margin(*player, game.current_player())
+            .map_or(-100, |margin| margin.clamp(-99, 99) as i8);
+    }
+    scores
 }
 
-#[derive(Debug)]
-pub enum MctsError {
-    GreedyError,
-    FoundIllegalMove(Move),
-    InDirtyState,
+fn won(winner: Player, players: u8) -> [i8; 4] {
+    let mut scores = [0; 4];
+    for player in Player::seats(players) {
+        scores[player.index()] = if *player == winner { 100 } else { -100 };
+    }
+    scores
 }
 
-struct QuoridorEvaluator;
+impl QuoridorEvaluator {
+    fn rollout<B: SearchBoard>(&self, game: &GameRecord<B>, rng: &mut SmallRng) -> [i8; 4] {
+        let mut game = game.clone();
+        let players = game.board().num_players();
+        let mut moves = quoridor_game::MoveList::new();
+        for _ in 0..MAX_ROLLOUT {
+            if let Some(winner) = game.winner() {
+                return won(winner, players);
+            }
+            let board = game.board();
+            let player = game.current_player();
+            if self.rollout == Rollout::Race
+                && board
+                    .players()
+                    .iter()
+                    .all(|p| board.available_walls(*p) == 0)
+            {
+                // with no walls left, whoever finishes first wins
+                let leader = board
+                    .players()
+                    .iter()
+                    .max_by_key(|p| board.race_margin(**p, player))
+                    .expect("there are players");
+                return won(*leader, players);
+            }
+            let greedy = match self.rollout {
+                Rollout::Greedy | Rollout::Mixed if !rng.gen_bool(GREEDY_EPSILON) => {
+                    greedy::best_move(board.clone(), player).ok()
+                }
+                _ => None,
+            };
+            let mov = match greedy {
+                Some(mov) => mov,
+                None => {
+                    moves.clear();
+                    game.generate_moves(&mut moves);
+                    if moves.is_empty() {
+                        break;
+                    }
+                    moves[rng.gen_range(0..moves.len())].clone()
+                }
+            };
+            game.apply_move(&mov).expect("rollout moves are legal");
+        }
+        static_scores(&game)
+    }
+}
 
-impl Evaluator<QuoridorSpec<BoardV2>> for QuoridorEvaluator {
-    type StateEvaluation = i8;
+impl<B: SearchBoard> Evaluator<QuoridorSpec<B>> for QuoridorEvaluator {
+    /// Score from the point of view of each seat, indexed by `Player::index`.
+    type StateEvaluation = [i8; 4];
 
     fn evaluate_new_state(
         &self,
-        state: &QuoridorState<BoardV2>,
-        moves: &Vec<Move>,
-        _: Option<SearchHandle<QuoridorSpec<BoardV2>>>,
-    ) -> (Vec<()>, i8) {
-        let score = match state {
-            QuoridorState::Dirty { offender } => match offender {
-                Player::Player1 => -100,
-                Player::Player2 => 100,
-            },
-            QuoridorState::Clean {
-                board,
-                current_player: _,
-            } => {
-                board.distance_to_goal(Player::Player2).unwrap_or(100) as i8
-                    - board
-                        .distance_to_goal(Player::Player1)
-                        .map(|x| x as i8)
-                        .unwrap_or(-100)
+        state: &QuoridorState<B>,
+        moves: &quoridor_game::MoveList,
+        handle: Option<SearchHandle<QuoridorSpec<B>>>,
+    ) -> (Vec<Prior>, [i8; 4]) {
+        let mut scores = [0; 4];
+        let mut priors = vec![Prior::default(); moves.len()];
+        match state {
+            QuoridorState::Dirty { offender, players } => {
+                for player in Player::seats(*players) {
+                    scores[player.index()] = if player == offender { -100 } else { 100 };
+                }
+            }
+            QuoridorState::Clean { game } => {
+                let board = game.board();
+                scores = match (self.rollout, handle) {
+                    (Rollout::Static, _) => static_scores(game),
+                    (Rollout::Mixed, Some(mut handle)) => {
+                        let rolled = self.rollout(game, &mut handle.thread_data().extra_data.0);
+                        let mut mixed = static_scores(game);
+                        for (score, rolled) in mixed.iter_mut().zip(&rolled) {
+                            *score = ((i16::from(*score) + i16::from(*rolled)) / 2) as i8;
+                        }
+                        mixed
+                    }
+                    (_, Some(mut handle)) => {
+                        self.rollout(game, &mut handle.thread_data().extra_data.0)
+                    }
+                    // only the root comes without a handle, and its score isn't used
+                    (_, None) => static_scores(game),
+                };
+                if !self.priors.is_empty() {
+                    for (prior, mov) in priors.iter_mut().zip(moves) {
+                        let mut board = board.clone();
+                        if board.make_move(mov, game.current_player()).is_ok() {
+                            let next = game.current_player().next(board.num_players());
+                            if let Some(found) = self.priors.get(&board.position_key(next)) {
+                                *prior = *found;
+                            }
+                        }
+                    }
+                }
             }
         };
-        (vec![(); moves.len()], score)
+        (priors, scores)
     }
-    fn interpret_evaluation_for_player(&self, evaln: &i8, player: &Player) -> i64 {
-        let score = match player {
-            Player::Player1 => *evaln,
-            Player::Player2 => -1 * *evaln,
-        };
-        score as i64
+    fn interpret_evaluation_for_player(&self, evaln: &[i8; 4], player: &Player) -> i64 {
+        evaln[player.index()] as i64
     }
     fn evaluate_existing_state(
         &self,
-        _: &QuoridorState<BoardV2>,
-        evaln: &i8,
-        _: SearchHandle<QuoridorSpec<BoardV2>>,
-    ) -> i8 {
+        _: &QuoridorState<B>,
+        evaln: &[i8; 4],
+        _: SearchHandle<QuoridorSpec<B>>,
+    ) -> [i8; 4] {
         *evaln
     }
 }
 
-impl TranspositionHash for QuoridorState<BoardV2> {
+impl<B: SearchBoard> TranspositionHash for QuoridorState<B> {
     fn hash(&self) -> u64 {
         match self {
-            QuoridorState::Dirty { offender: _ } => 0,
-            QuoridorState::Clean {
-                current_player,
-                board,
-            } => {
-                let mut hasher = FxHasher::default();
-                hasher.write_u8(match current_player {
-                    Player::Player1 => 177,
-                    Player::Player2 => 87,
-                });
-                board.fx_hash(&mut hasher);
-                hasher.finish()
-            }
+            QuoridorState::Dirty { .. } => 0,
+            QuoridorState::Clean { game } => game.position_key(),
         }
     }
 }
 
-#[derive(Default)]
-struct QuoridorSpec<B>(PhantomData<B>);
+struct QuoridorSpec<B> {
+    node_limit: usize,
+    board: PhantomData<B>,
+}
 
-impl MCTS for QuoridorSpec<BoardV2> {
-    type State = QuoridorState<BoardV2>;
+impl<B: SearchBoard> MCTS for QuoridorSpec<B> {
+    type State = QuoridorState<B>;
     type Eval = QuoridorEvaluator;
     type NodeData = ();
-    type ExtraThreadData = ();
-    type TreePolicy = UCTPolicy;
+    type ExtraThreadData = RolloutRng;
+    type TreePolicy = WarmUctPolicy;
     type TranspositionTable = ApproxTable<Self>;
 
+    fn node_limit(&self) -> usize {
+        self.node_limit
+    }
+
+    fn select_child_after_search<'a>(&self, children: &'a [MoveInfo<Self>]) -> &'a MoveInfo<Self> {
+        children.iter().max_by_key(|child| visits(child)).unwrap()
+    }
+
     fn cycle_behaviour(&self) -> CycleBehaviour<Self> {
         CycleBehaviour::UseCurrentEvalWhenCycleDetected
     }
@@ -115,82 +458,208 @@ impl MCTS for QuoridorSpec<BoardV2> {
 impl<B: Board + Clone + Hash + Eq> QuoridorState<B> {
     pub fn new(board: B) -> Self {
         Self::Clean {
-            board,
-            current_player: Player::Player1,
+            game: GameRecord::new(board),
         }
     }
 }
 
-impl MctsAiPlayer {
-    pub fn new(board: BoardV2, think_time: u32) -> Self {
+impl<B: SearchBoard> MctsAiPlayer<B> {
+    pub fn new(board: B, playouts: u32) -> Self {
+        Self::with_game(GameRecord::new(board), playouts)
+    }
+
+    /// The default settings with a budget of `playouts`.
+    pub fn with_game(game: GameRecord<B>, playouts: u32) -> Self {
+        Self::with_config(
+            game,
+            MctsConfig {
+                budget: MctsBudget::Playouts(playouts),
+                ..MctsConfig::default()
+            },
+        )
+    }
+
+    pub fn with_config(game: GameRecord<B>, config: MctsConfig) -> Self {
+        let priors = Arc::new(Priors::new());
         Self {
-            state: QuoridorState::new(board.clone()),
-            mcts: MCTSManager::new(
-                QuoridorState::new(board),
-                QuoridorSpec(PhantomData::default()),
-                QuoridorEvaluator,
-                UCTPolicy::new(0.2),
-                ApproxTable::new(1024),
-            ),
-            think_time: think_time,
+            state: QuoridorState::Clean { game: game.clone() },
+            root_ply: game.ply(),
+            mcts: new_manager(game, &priors, &config, usize::MAX),
+            config,
+            priors,
         }
     }
+
+    pub fn config(&self) -> &MctsConfig {
+        &self.config
+    }
+
+    /// Runs a search from the current position within `budget`, starting
+    /// from what the last search learned about it.
+    fn search(&mut self, budget: MctsBudget) {
+        let node_limit = match budget {
+            MctsBudget::Nodes(nodes) => nodes,
+            _ => usize::MAX,
+        };
+        self.priors = Arc::new(self.reroot());
+        self.mcts = new_manager(self.game().clone(), &self.priors, &self.config, node_limit);
+        self.root_ply = self.game().ply();
+        let threads = self.config.threads;
+        match budget {
+            MctsBudget::Playouts(playouts) => self.mcts.playout_n_parallel(playouts, threads),
+            MctsBudget::Deadline(time) => self.mcts.playout_parallel_for(time, threads),
+            // each playout adds at most one node
+            MctsBudget::Nodes(nodes) => loop {
+                let before = self.mcts.tree().num_nodes();
+                if before >= nodes {
+                    break;
+                }
+                let playouts = (nodes - before).max(threads).min(u32::MAX as usize);
+                self.mcts.playout_n_parallel(playouts as u32, threads);
+                if self.mcts.tree().num_nodes() == before {
+                    break;
+                }
+            },
+        }
+    }
+
+    /// Finds the current position in the last tree by following the moves
+    /// played since its root, and collects what was learned below it. A
+    /// tree can't adopt a node of another one, so this is how the next
+    /// search re-roots there. Empty when the position wasn't explored.
+    fn reroot(&self) -> Priors {
+        let played = self.game().moves().skip(self.root_ply).collect::<Vec<_>>();
+        let mut priors = Priors::new();
+        harvest_after(
+            self.mcts.tree().root_node(),
+            &played,
+            &mut self.game().clone(),
+            &mut priors,
+        );
+        priors
+    }
 }
 
-impl MctsAiPlayer {
-    pub fn send(&mut self, m: &Move) -> Result<(), MctsError> {
-        match &mut self.state {
-            QuoridorState::Clean {
-                current_player,
-                board,
-            } => {
-                board
-                    .apply_move(m, *current_player)
-                    .map_err(|_| MctsError::FoundIllegalMove(m.clone()))?;
-                *current_player = current_player.other();
-                Ok(())
+fn new_manager<B: SearchBoard>(
+    game: GameRecord<B>,
+    priors: &Arc<Priors>,
+    config: &MctsConfig,
+    node_limit: usize,
+) -> MCTSManager<QuoridorSpec<B>> {
+    let mut manager = MCTSManager::new(
+        QuoridorState::Clean { game },
+        QuoridorSpec {
+            node_limit,
+            board: PhantomData,
+        },
+        QuoridorEvaluator {
+            priors: priors.clone(),
+            rollout: config.rollout,
+        },
+        WarmUctPolicy(config.exploration),
+        ApproxTable::new(config.table_size),
+    );
+    manager.print_on_playout_error(false);
+    manager
+}
+
+/// Follows `played` down from `node` and harvests the node it ends on, if
+/// the search got that far.
+fn harvest_after<B: SearchBoard>(
+    node: NodeHandle<QuoridorSpec<B>>,
+    played: &[&Move],
+    game: &mut GameRecord<B>,
+    priors: &mut Priors,
+) {
+    let (mov, rest) = match played.split_first() {
+        Some(split) => split,
+        None => return harvest(node, game, priors),
+    };
+    let child = node
+        .moves()
+        .find(|m| m.get_move() == *mov)
+        .and_then(|m| m.child());
+    if let Some(child) = child {
+        harvest_after(child, rest, game, priors);
+    }
+}
+
+/// Collects the visits and rewards of every explored move below `node`,
+/// including the priors the move started with. Positions reached twice are
+/// only walked once.
+fn harvest<B: SearchBoard>(
+    node: NodeHandle<QuoridorSpec<B>>,
+    game: &mut GameRecord<B>,
+    priors: &mut Priors,
+) {
+    for mov in node.moves() {
+        if mov.visits() == 0 || game.apply_move(mov.get_move()).is_err() {
+            continue;
+        }
+        let key = game.position_key();
+        if let Entry::Vacant(entry) = priors.entry(key) {
+            let prior = mov.move_evaluation();
+            entry.insert(Prior {
+                visits: mov.visits() + prior.visits,
+                sum_rewards: mov.sum_rewards() + prior.sum_rewards,
+            });
+            if let Some(child) = mov.child() {
+                harvest(child, game, priors);
             }
-            QuoridorState::Dirty { offender: _ } => Err(MctsError::InDirtyState),
         }
+        game.undo();
     }
+}
 
-    pub fn receive(&mut self) -> Result<Move, MctsError> {
-        match &mut self.state {
-            QuoridorState::Clean {
-                current_player,
-                board,
-            } => {
-                self.mcts = MCTSManager::new(
-                    QuoridorState::Clean {
-                        current_player: current_player.clone(),
-                        board: board.clone(),
-                    },
-                    QuoridorSpec(PhantomData::default()),
-                    QuoridorEvaluator,
-                    UCTPolicy::new(0.2),
-                    ApproxTable::new(4096),
-                );
-                self.mcts.playout_n_parallel(self.think_time, 16); // 10000 playouts, 4 search threads
-                let m = if let Some(m) = self.mcts.best_move() {
-                    m
-                } else {
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                    greedy::best_move(board.clone(), *current_player)
-                        .map_err(|_| MctsError::GreedyError)?
-                };
-                board
-                    .apply_move(&m, *current_player)
-                    .map_err(|_| MctsError::FoundIllegalMove(m.clone()))?;
-                *current_player = current_player.other();
+/// `Budget::Default` stands for the budget in its config, `Budget::Nodes` for
+/// playouts and `Budget::Time` for a deadline.
+impl<B: SearchBoard> Agent<B> for MctsAiPlayer<B> {
+    fn new_game(&mut self, game: GameRecord<B>) {
+        *self = Self::with_config(game, self.config.clone());
+    }
 
-                Ok(m)
-            }
-            QuoridorState::Dirty { offender: _ } => Err(MctsError::InDirtyState),
+    fn observe(&mut self, mov: &Move) -> Result<(), AgentError> {
+        agent::apply(self.game_mut(), mov)
+    }
+
+    fn choose(&mut self, budget: Budget) -> Result<Move, AgentError> {
+        let game = self.game_mut();
+        if game.winner().is_some() {
+            return Err(AgentError::NoMove);
+        }
+        let game = game.clone();
+        self.search(match budget {
+            Budget::Default => self.config.budget,
+            Budget::Nodes(playouts) => MctsBudget::Playouts(playouts.min(u32::MAX as u64) as u32),
+            Budget::Time(time) => MctsBudget::Deadline(time),
+        });
+        let m = match self.mcts.best_move() {
+            Some(m) => m,
+            None => greedy::best_move(game.board().clone(), game.current_player())
+                .map_err(|_| AgentError::NoMove)?,
+        };
+        agent::apply(self.game_mut(), &m)?;
+        Ok(m)
+    }
+
+    fn game(&self) -> &GameRecord<B> {
+        match &self.state {
+            QuoridorState::Clean { game } => game,
+            QuoridorState::Dirty { .. } => unreachable!("only searches reach dirty states"),
+        }
+    }
+}
+
+impl<B: SearchBoard> MctsAiPlayer<B> {
+    fn game_mut(&mut self) -> &mut GameRecord<B> {
+        match &mut self.state {
+            QuoridorState::Clean { game } => game,
+            QuoridorState::Dirty { .. } => unreachable!("only searches reach dirty states"),
         }
     }
 
     pub fn debug(&mut self) {
-        self.mcts.playout_n_parallel(1000000, 16); // 10000 playouts, 4 search threads
+        self.mcts.playout_n_parallel(1000000, self.config.threads);
         dbg!(self.mcts.principal_variation(100));
     }
 }
@@ -198,56 +667,142 @@ impl MctsAiPlayer {
 impl<B: Board + Clone + Hash + Eq + Clone + Debug> GameState for QuoridorState<B> {
     type Move = Move;
     type Player = Player;
-    type MoveList = Vec<Move>;
+    type MoveList = quoridor_game::MoveList;
 
     fn current_player(&self) -> Self::Player {
         match self {
-            QuoridorState::Clean {
-                current_player,
-                board: _,
-            } => *current_player,
-            QuoridorState::Dirty { offender } => offender.other(),
+            QuoridorState::Clean { game } => game.current_player(),
+            QuoridorState::Dirty { offender, players } => offender.next(*players),
         }
     }
-    fn available_moves(&self) -> Vec<Move> {
-        match self {
-            QuoridorState::Clean {
-                current_player,
-                board,
-            } => {
-                if board.player_location(Player::Player1).1 == 8 {
-                    return vec![];
-                }
-                if board.player_location(Player::Player2).1 == 0 {
-                    return vec![];
-                }
-                if board.available_walls(Player::Player1) == 0
-                    && board.available_walls(Player::Player2) == 0
-                {
-                    return vec![];
-                }
-                board.legal_moves(*current_player)
-            }
-            QuoridorState::Dirty { offender: _ } => {
-                vec![]
+    fn available_moves(&self) -> quoridor_game::MoveList {
+        let mut moves = quoridor_game::MoveList::new();
+        if let QuoridorState::Clean { game } = self {
+            let board = game.board();
+            if board
+                .players()
+                .iter()
+                .any(|p| board.available_walls(*p) != 0)
+            {
+                game.generate_moves(&mut moves);
             }
         }
+        moves
     }
     fn make_move(&mut self, mov: &Self::Move) {
         match self {
-            QuoridorState::Clean {
-                current_player,
-                board,
-            } => {
-                let move_result = board.apply_move(mov, *current_player);
-                *current_player = current_player.other();
-                if move_result.is_err() {
+            QuoridorState::Clean { game } => {
+                if game.apply_move(mov).is_err() {
                     *self = QuoridorState::Dirty {
-                        offender: *current_player,
+                        offender: game.current_player(),
+                        players: game.board().num_players(),
                     };
                 }
             }
-            QuoridorState::Dirty { offender: _ } => (),
+            QuoridorState::Dirty { .. } => (),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_and_writes_configs() {
+        for config in &[
+            "10000",
+            "500ms",
+            "2s,threads=2",
+            "50000nodes,exploration=0.5,table=8192,rollout=race",
+        ] {
+            assert_eq!(*config, config.parse::<MctsConfig>().unwrap().to_string());
+        }
+        let config: MctsConfig = "1500ms,threads=2".parse().unwrap();
+        assert_eq!(
+            MctsBudget::Deadline(Duration::from_millis(1500)),
+            config.budget
+        );
+        assert_eq!(2, config.threads);
+        assert_eq!(MctsConfig::default().table_size, config.table_size);
+
+        for bad in &[
+            "",
+            "fast",
+            "10000,threads=0",
+            "10000,exploration=-1",
+            "10000,exploration=NaN",
+            "0",
+            "0ms",
+            "99999999999",
+            "10000,table=99999999999999999999",
+            "10000,depth=3",
+            "10000,threads",
+            "10000,rollout=slow",
+        ] {
+            assert!(bad.parse::<MctsConfig>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_node_limit() {
+        let mut player = MctsAiPlayer::with_config(
+            GameRecord::new(BoardV2::with_players(5, 2).unwrap()),
+            "300nodes,threads=2".parse().unwrap(),
+        );
+        player.choose(Budget::Default).unwrap();
+        let nodes = player.mcts.tree().num_nodes();
+        assert!((300..340).contains(&nodes), "{}", nodes);
+    }
+
+    #[test]
+    fn plays_with_every_rollout() {
+        for rollout in &["static", "random", "greedy", "race", "mixed"] {
+            let config = format!("200,threads=2,rollout={}", rollout)
+                .parse()
+                .unwrap();
+            let game = GameRecord::<BoardV2>::from_fen("- - c1,c5 2,2 1 5").unwrap();
+            let mut player = MctsAiPlayer::with_config(game, config);
+            let mov = player.choose(Budget::Default).unwrap();
+            assert_eq!(1, player.game().ply(), "{} played {}", rollout, mov);
+        }
+    }
+
+    fn root_visits(player: &MctsAiPlayer) -> u64 {
+        player.mcts.tree().root_node().moves().map(visits).sum()
+    }
+
+    #[test]
+    fn reuses_the_tree_after_a_reply() {
+        let game = GameRecord::<BoardV2>::from_fen("- - c1,c5 2,2 1 5").unwrap();
+        let mut player = MctsAiPlayer::with_config(game, "2000,threads=1".parse().unwrap());
+        let mov = player.choose(Budget::Default).unwrap();
+        let (reply, explored) = {
+            let root = player.mcts.tree().root_node();
+            let ours = root.moves().find(|m| *m.get_move() == mov).unwrap();
+            let replies = ours.child().unwrap();
+            let reply = replies.moves().max_by_key(|m| visits(m)).unwrap();
+            let after = reply.child().unwrap();
+            let explored = after.moves().map(visits).sum::<u64>();
+            (reply.get_move().clone(), explored)
+        };
+        assert!(explored > 0);
+
+        player.observe(&reply).unwrap();
+        player.search(MctsBudget::Playouts(100));
+        assert_eq!(explored + 100, root_visits(&player));
+
+        // a reply the last search never looked at starts a fresh tree
+        let mov = player.choose(Budget::Nodes(100)).unwrap();
+        let unexplored = {
+            let root = player.mcts.tree().root_node();
+            let ours = root.moves().find(|m| *m.get_move() == mov).unwrap();
+            let replies = ours.child().unwrap();
+            let reply = replies.moves().find(|m| m.visits() == 0).unwrap();
+            reply.get_move().clone()
+        };
+        player.observe(&unexplored).unwrap();
+        player.search(MctsBudget::Playouts(100));
+        assert_eq!(100, root_visits(&player));
+    }
+}
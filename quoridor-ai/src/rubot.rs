@@ -1,28 +1,34 @@
-use quoridor_game::{Board, Move, Player};
+use super::greedy::{self, closest_opponent_distance};
+use crate::agent::{self, Agent, AgentError, Budget};
+use quoridor_game::{Board, GameRecord, IllegalMove, Move, MoveList, Player};
+use std::{hash::Hash, time::Duration};
 
 #[derive(Clone)]
 pub struct QuoridorGame<B: Board> {
-    board: B,
-    current_player: Player,
+    game: GameRecord<B>,
 }
 
-impl<B: Board> QuoridorGame<B> {
+impl<B: Board + Clone> QuoridorGame<B> {
     pub fn new() -> QuoridorGame<B> {
-        QuoridorGame {
-            board: B::empty(),
-            current_player: Player::Player1,
-        }
+        QuoridorGame::with_board(B::empty())
+    }
+    pub fn with_board(board: B) -> QuoridorGame<B> {
+        QuoridorGame::with_game(GameRecord::new(board))
+    }
+    pub fn with_game(game: GameRecord<B>) -> QuoridorGame<B> {
+        QuoridorGame { game }
     }
-    pub fn apply_move(&mut self, mov: &Move) -> Result<(), ()> {
-        self.board.apply_move(mov, self.current_player)?;
-        self.current_player = self.current_player.other();
-        Ok(())
+    pub fn apply_move(&mut self, mov: &Move) -> Result<(), IllegalMove> {
+        self.game.apply_move(mov)
     }
     pub fn current_player(&self) -> Player {
-        self.current_player
+        self.game.current_player()
     }
     pub fn board(&self) -> &B {
-        &self.board
+        self.game.board()
+    }
+    pub fn game(&self) -> &GameRecord<B> {
+        &self.game
     }
 }
 
@@ -30,34 +36,29 @@ impl<B: Board + Clone> rubot::Game for QuoridorGame<B> {
     type Player = Player;
     type Action = Move;
     type Fitness = i8;
-    type Actions = Vec<Move>;
+    type Actions = MoveList;
 
     fn actions(&self, player: Self::Player) -> (bool, Self::Actions) {
-        (
-            player == self.current_player,
-            if self.board.player_location(Player::Player1).1 == 8 {
-                vec![]
-            } else if self.board.player_location(Player::Player2).1 == 0 {
-                vec![]
-            } else if self.board.available_walls(Player::Player1) == 0
-                && self.board.available_walls(Player::Player2) == 0
-            {
-                vec![]
-            } else {
-                self.board.legal_moves(self.current_player)
-            },
-        )
+        let mut moves = MoveList::new();
+        if self
+            .board()
+            .players()
+            .iter()
+            .any(|p| self.board().available_walls(*p) != 0)
+        {
+            self.game.generate_moves(&mut moves);
+        }
+        (player == self.current_player(), moves)
     }
 
     fn execute(&mut self, action: &Self::Action, player: Self::Player) -> Self::Fitness {
         self.apply_move(action).unwrap();
-        self.board
-            .distance_to_goal(player.other())
-            .zip(self.board.distance_to_goal(player))
+        closest_opponent_distance(self.board(), player)
+            .zip(self.board().distance_to_goal(player))
             .map(|(them, me)| {
-                if them < 2 && player.other() == self.current_player {
+                if them < 2 && player != self.current_player() {
                     -100
-                } else if me < 2 && player == self.current_player {
+                } else if me < 2 && player == self.current_player() {
                     100
                 } else {
                     them as i8 - me as i8
@@ -66,3 +67,59 @@ impl<B: Board + Clone> rubot::Game for QuoridorGame<B> {
             .unwrap_or(100)
     }
 }
+
+/// Alpha-beta search from the `rubot` crate, falling back on the greedy bot
+/// when the search comes up empty.
+pub struct RubotAiPlayer<B: Board> {
+    game: QuoridorGame<B>,
+    budget: Budget,
+}
+
+impl<B: Board + Clone + Hash + Eq> RubotAiPlayer<B> {
+    /// `budget` is what `Budget::Default` stands for; rubot counts nodes in
+    /// steps of its search.
+    pub fn new(game: GameRecord<B>, budget: Budget) -> Self {
+        Self {
+            game: QuoridorGame::with_game(game),
+            budget,
+        }
+    }
+}
+
+impl<B: Board + Clone + Hash + Eq> Agent<B> for RubotAiPlayer<B> {
+    fn new_game(&mut self, game: GameRecord<B>) {
+        self.game = QuoridorGame::with_game(game);
+    }
+
+    fn observe(&mut self, mov: &Move) -> Result<(), AgentError> {
+        agent::apply(&mut self.game.game, mov)
+    }
+
+    fn choose(&mut self, budget: Budget) -> Result<Move, AgentError> {
+        let player = self.game.current_player();
+        if self.game.game.winner().is_some() {
+            return Err(AgentError::NoMove);
+        }
+        let mut bot = ::rubot::Bot::new(player);
+        let found = match (budget, self.budget) {
+            (Budget::Time(time), _) | (Budget::Default, Budget::Time(time)) => {
+                bot.select(&self.game, time)
+            }
+            (Budget::Nodes(steps), _) | (Budget::Default, Budget::Nodes(steps)) => {
+                bot.select(&self.game, ::rubot::Steps(steps as u32))
+            }
+            (Budget::Default, Budget::Default) => bot.select(&self.game, Duration::from_secs(1)),
+        };
+        let mov = match found {
+            Some(mov) => mov,
+            None => greedy::best_move(self.game.board().clone(), player)
+                .map_err(|_| AgentError::NoMove)?,
+        };
+        agent::apply(&mut self.game.game, &mov)?;
+        Ok(mov)
+    }
+
+    fn game(&self) -> &GameRecord<B> {
+        &self.game.game
+    }
+}
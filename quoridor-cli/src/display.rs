@@ -54,8 +54,8 @@ impl From<ErrorKind> for DisplayError {
 
 type DisplayCell = Cell<DisplayWallState>;
 
-fn display_cell(cell: &DisplayCell, (x, y): (u8, u8)) -> Result<(), DisplayError> {
-    if x != 8 {
+fn display_cell(cell: &DisplayCell, (x, y): (u8, u8), size: u8) -> Result<(), DisplayError> {
+    if x != size - 1 {
         queue!(
             stdout(),
             crossterm::cursor::MoveTo((3 * x + 2) as u16, (2 * y) as u16),
@@ -63,14 +63,14 @@ fn display_cell(cell: &DisplayCell, (x, y): (u8, u8)) -> Result<(), DisplayError
             Print("|")
         )?;
     }
-    if y != 8 {
+    if y != size - 1 {
         queue!(
             stdout(),
             crossterm::cursor::MoveTo((3 * x + 0) as u16, (2 * y + 1) as u16),
             SetForegroundColor(cell.bottom.to_color()),
             Print("--")
         )?;
-        if x != 8 {
+        if x != size - 1 {
             queue!(
                 stdout(),
                 SetForegroundColor(cell.joint.to_color()),
@@ -124,6 +124,7 @@ impl Display {
         queue!(stdout(), Clear(ClearType::All))?;
         display(&board, Some((player, candidate_move)))?;
         stdout().flush()?;
+        let last_wall = board.size() - 2;
         loop {
             if let Event::Key(event) = read()? {
                 match event.code {
@@ -140,7 +141,7 @@ impl Display {
                                 orientation: _,
                             } => Move::MoveTo(0, 0),
                             Move::MoveTo(_, _) => Move::AddWall {
-                                location: (4, 4),
+                                location: (board.size() / 2, board.size() / 2),
                                 orientation: Orientation::Horizontal,
                             },
                         }
@@ -171,7 +172,7 @@ impl Display {
                         Move::AddWall {
                             orientation: _,
                             location: (x, _),
-                        } => *x = if *x < 7 { *x + 1 } else { 7 },
+                        } => *x = if *x < last_wall { *x + 1 } else { last_wall },
                         Move::MoveTo(_, _) => {
                             *candidate_move = Move::MoveTo(
                                 board.player_location(*player).0 + 1,
@@ -197,7 +198,7 @@ impl Display {
                         Move::AddWall {
                             orientation: _,
                             location: (_, y),
-                        } => *y = if *y < 7 { *y + 1 } else { 7 },
+                        } => *y = if *y < last_wall { *y + 1 } else { last_wall },
                         Move::MoveTo(_, _) => {
                             *candidate_move = Move::MoveTo(
                                 board.player_location(*player).0,
@@ -237,7 +238,8 @@ impl Display {
 }
 
 fn display(board: &BoardV1, player_and_move: Option<(&Player, &Move)>) -> Result<(), DisplayError> {
-    for (y, cells) in board.cells.iter().enumerate() {
+    let size = board.size();
+    for (y, cells) in board.rows().enumerate() {
         let y = y as u8;
         for (x, cell) in cells.iter().enumerate() {
             let x = x as u8;
@@ -293,19 +295,28 @@ fn display(board: &BoardV1, player_and_move: Option<(&Player, &Move)>) -> Result
                 }
             }
 
-            display_cell(&cell, (x, y))?;
+            display_cell(&cell, (x, y), size)?;
         }
     }
 
-    for player in [Player::Player1, Player::Player2].iter() {
+    for player in board.players() {
+        for (x, y) in board.shortest_path(*player).unwrap_or_default() {
+            queue!(
+                stdout(),
+                crossterm::cursor::MoveTo((3 * x + 1) as u16, (2 * y) as u16),
+                SetForegroundColor(route_color(*player)),
+                Print(".")
+            )?;
+        }
+    }
+
+    queue!(stdout(), SetForegroundColor(crossterm::style::Color::White))?;
+    for player in board.players() {
         let loc = board.location(player);
         queue!(
             stdout(),
             crossterm::cursor::MoveTo((3 * loc.0) as u16, (2 * loc.1) as u16),
-            match player {
-                Player::Player1 => Print("v"),
-                Player::Player2 => Print("^"),
-            }
+            Print(glyph(*player))
         )?;
     }
 
@@ -324,28 +335,40 @@ fn display(board: &BoardV1, player_and_move: Option<(&Player, &Move)>) -> Result
 
     // let packed_board: crate::bitpacked::BoardV2 = board.clone().into();
 
-    queue!(
-        stdout(),
-        SetForegroundColor(crossterm::style::Color::White),
-        crossterm::cursor::MoveTo(0, 18),
-        Print(format!(
-            "1 (v) has {} walls and {} steps to go",
-            board.available_walls(crate::Player::Player1),
-            board
-                .distance_to_goal(crate::Player::Player1)
-                .unwrap_or(u8::MAX)
-        )),
-        crossterm::cursor::MoveTo(0, 19),
-        Print(format!(
-            "2 (^) has {} walls and {} steps to go",
-            board.available_walls(crate::Player::Player2),
-            board
-                .distance_to_goal(crate::Player::Player2)
-                .unwrap_or(u8::MAX)
-        )),
-    )?;
+    queue!(stdout(), SetForegroundColor(crossterm::style::Color::White))?;
+    for (row, player) in board.players().iter().enumerate() {
+        queue!(
+            stdout(),
+            crossterm::cursor::MoveTo(0, 2 * size as u16 + row as u16),
+            Print(format!(
+                "{} ({}) has {} walls and {} steps to go",
+                player.index() + 1,
+                glyph(*player),
+                board.available_walls(*player),
+                board.distance_to_goal(*player).unwrap_or(u8::MAX)
+            )),
+        )?;
+    }
 
     stdout().flush()?;
 
     Ok(())
 }
+
+fn glyph(player: Player) -> &'static str {
+    match player {
+        Player::Player1 => "v",
+        Player::Player2 => "^",
+        Player::Player3 => "<",
+        Player::Player4 => ">",
+    }
+}
+
+fn route_color(player: Player) -> crossterm::style::Color {
+    match player {
+        Player::Player1 => crossterm::style::Color::Cyan,
+        Player::Player2 => crossterm::style::Color::Magenta,
+        Player::Player3 => crossterm::style::Color::DarkGreen,
+        Player::Player4 => crossterm::style::Color::DarkYellow,
+    }
+}
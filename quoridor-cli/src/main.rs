@@ -1,23 +1,31 @@
-use clap::{AppSettings, Clap};
+use clap::{AppSettings, ArgSettings, Clap};
 use display::DisplayError;
 use parse_display::{Display, FromStr};
-use quoridor_ai::{greedy, mcts::MctsAiPlayer};
-use quoridor_ai::{greedy::GreedyAiPlayer, mcts::MctsError};
+use quoridor_ai::{
+    alphabeta::AlphaBetaAiPlayer,
+    greedy::GreedyAiPlayer,
+    mcts::{MctsAiPlayer, MctsConfig, SearchBoard},
+    rubot::RubotAiPlayer,
+};
+use quoridor_ai::{Agent, AgentError, Budget};
 use quoridor_game::bitpacked::BoardV2;
+use quoridor_game::v1::BoardV1;
 use quoridor_game::*;
-use std::{
-    convert::{TryFrom, TryInto},
-    hash::Hash,
-};
+use std::time::Duration;
 use tcp::GameError;
 
 #[derive(Debug)]
 pub enum Error {
-    InvalidMoveAttempted,
-    CantFindMoveError,
-    MctsError(MctsError),
+    IllegalMove(Player, Move, IllegalMove),
+    UnsupportedBoardSize(u8),
+    UnsupportedPlayerCount,
+    /// This kind of player can't play a four player game.
+    TwoPlayersOnly(String),
+    BadPosition(fen::FenError),
+    Agent(AgentError),
     DisplayError(DisplayError),
     TcpError(tcp::GameError),
+    SaveError(std::io::Error),
 }
 
 impl From<DisplayError> for Error {
@@ -25,6 +33,11 @@ impl From<DisplayError> for Error {
         Error::DisplayError(e)
     }
 }
+impl From<AgentError> for Error {
+    fn from(e: AgentError) -> Error {
+        Error::Agent(e)
+    }
+}
 impl From<GameError> for Error {
     fn from(e: GameError) -> Error {
         Error::TcpError(e)
@@ -36,28 +49,6 @@ pub trait RemotePlayer {
     fn receive(&mut self) -> Result<Move, Error>;
 }
 
-impl<B: Board + Clone + Eq + Hash> RemotePlayer for quoridor_ai::rubot::QuoridorGame<B> {
-    fn send(&mut self, m: &Move) -> Result<(), Error> {
-        self.apply_move(m).map_err(|_| Error::InvalidMoveAttempted)
-    }
-
-    fn receive(&mut self) -> Result<Move, Error> {
-        let mov = if let Some(mov) =
-            rubot::Bot::new(self.current_player()).select(self, std::time::Duration::from_secs(1))
-        {
-            mov
-        } else {
-            greedy::best_move(self.board().clone(), self.current_player())
-                .map_err(|_| Error::CantFindMoveError)?
-        };
-
-        self.apply_move(&mov)
-            .map_err(|_| Error::InvalidMoveAttempted)?;
-
-        Ok(mov)
-    }
-}
-
 impl RemotePlayer for tcp::Game {
     fn send(&mut self, m: &Move) -> Result<(), Error> {
         tcp::Game::send(self, m).map_err(Error::TcpError)
@@ -67,32 +58,51 @@ impl RemotePlayer for tcp::Game {
     }
 }
 
-impl<B: Board + Clone + Hash + Eq> RemotePlayer for quoridor_ai::greedy::GreedyAiPlayer<B> {
-    fn send(&mut self, m: &Move) -> Result<(), Error> {
-        quoridor_ai::greedy::GreedyAiPlayer::send(self, m).map_err(|_| Error::InvalidMoveAttempted)
-    }
-    fn receive(&mut self) -> Result<Move, Error> {
-        quoridor_ai::greedy::GreedyAiPlayer::receive(self).map_err(|_| Error::InvalidMoveAttempted)
-    }
-}
-
-impl RemotePlayer for quoridor_ai::mcts::MctsAiPlayer {
-    fn send(&mut self, m: &Move) -> Result<(), Error> {
-        quoridor_ai::mcts::MctsAiPlayer::send(self, m).map_err(Error::MctsError)
-    }
-    fn receive(&mut self) -> Result<Move, Error> {
-        quoridor_ai::mcts::MctsAiPlayer::receive(self).map_err(Error::MctsError)
-    }
-}
-
 mod display;
 mod tcp;
 #[derive(Clap)]
 #[clap(version = "1.0", author = "Sheyne Anderson")]
 #[clap(setting = AppSettings::ColoredHelp)]
+#[clap(setting = AppSettings::SubcommandsNegateReqs)]
 struct Opts {
-    player1: PlayerKind,
-    player2: PlayerKind,
+    #[clap(setting = ArgSettings::Required)]
+    player1: Option<PlayerKind>,
+    #[clap(setting = ArgSettings::Required)]
+    player2: Option<PlayerKind>,
+    /// Giving a third and fourth player starts a four player game
+    player3: Option<PlayerKind>,
+    player4: Option<PlayerKind>,
+    #[clap(long, default_value = "9")]
+    size: u8,
+    /// Start from this position string, e.g. "e3 - e2,e8 9,10 2"
+    #[clap(long, allow_hyphen_values = true)]
+    position: Option<String>,
+    /// Write the game to this file in PGN style when it ends
+    #[clap(long)]
+    save: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clap)]
+enum Command {
+    Perft(Perft),
+}
+
+/// Count the move sequences of a given length, to check move generation
+#[derive(Clap)]
+struct Perft {
+    depth: u8,
+    #[clap(long, default_value = "9")]
+    size: u8,
+    #[clap(long, default_value = "2")]
+    players: u8,
+    /// Start from this position string instead of a new game
+    #[clap(long, allow_hyphen_values = true)]
+    position: Option<String>,
+    /// List the count after each first move
+    #[clap(long)]
+    divide: bool,
 }
 
 #[derive(FromStr, Display, Clone)]
@@ -101,8 +111,12 @@ enum PlayerKind {
     Keyboard,
     GreedyAi,
     Rubot,
+    /// MCTS with settings such as `10000` playouts or `2s,threads=2`
     #[display("mcts-ai-{0}")]
-    MctsAi(u32),
+    MctsAi(MctsConfig),
+    /// Alpha-beta search with this many nodes per move, in two player games
+    #[display("alpha-beta-{0}")]
+    AlphaBeta(u64),
     #[display("serve-{port}")]
     Serve {
         port: u16,
@@ -113,16 +127,25 @@ enum PlayerKind {
     },
 }
 
-enum PlayerDriver {
+/// A board the bots can search and the display can draw.
+trait PlayBoard: SearchBoard + Into<BoardV1> {}
+
+impl<B: SearchBoard + Into<BoardV1>> PlayBoard for B {}
+
+enum PlayerDriver<B> {
     RemotePlayer(Box<dyn RemotePlayer>),
+    Agent(Box<dyn Agent<B>>),
     Keyboard,
 }
 
-impl TryFrom<PlayerKind> for PlayerDriver {
-    type Error = Error;
-
-    fn try_from(kind: PlayerKind) -> Result<Self, Error> {
-        let board = BoardV2::empty();
+impl<B: PlayBoard> PlayerDriver<B> {
+    fn new(kind: PlayerKind, game: &GameRecord<B>) -> Result<Self, Error> {
+        let game = game.clone();
+        if let PlayerKind::AlphaBeta(_) = kind {
+            if game.board().num_players() > 2 {
+                return Err(Error::TwoPlayersOnly(kind.to_string()));
+            }
+        }
 
         Ok(match kind {
             PlayerKind::Serve { port } => {
@@ -131,44 +154,46 @@ impl TryFrom<PlayerKind> for PlayerDriver {
             PlayerKind::Connect { connect } => {
                 PlayerDriver::RemotePlayer(Box::new(tcp::Game::connect(connect)?))
             }
-            PlayerKind::GreedyAi => {
-                PlayerDriver::RemotePlayer(Box::new(GreedyAiPlayer::new(board, Player::Player1)))
+            PlayerKind::GreedyAi => PlayerDriver::Agent(Box::new(GreedyAiPlayer::with_game(game))),
+            PlayerKind::Rubot => PlayerDriver::Agent(Box::new(RubotAiPlayer::new(
+                game,
+                Budget::Time(Duration::from_secs(1)),
+            ))),
+            PlayerKind::MctsAi(config) => {
+                PlayerDriver::Agent(Box::new(MctsAiPlayer::with_config(game, config)))
             }
-            PlayerKind::Rubot => PlayerDriver::RemotePlayer(Box::new(
-                quoridor_ai::rubot::QuoridorGame::<BoardV2>::new(),
-            )),
-            PlayerKind::MctsAi(t) => {
-                PlayerDriver::RemotePlayer(Box::new(MctsAiPlayer::new(board, t)))
+            PlayerKind::AlphaBeta(nodes) => {
+                PlayerDriver::Agent(Box::new(AlphaBetaAiPlayer::new(game, Budget::Nodes(nodes))))
             }
             PlayerKind::Keyboard => PlayerDriver::Keyboard,
         })
     }
 }
 
-struct Main {
-    player1: PlayerDriver,
-    player2: PlayerDriver,
+struct Main<B> {
+    drivers: Vec<PlayerDriver<B>>,
     display: display::Display,
-    board: BoardV2,
+    game: GameRecord<B>,
     candidate: Move,
 }
 
-impl Main {
-    fn driver(&mut self, p: Player) -> &mut PlayerDriver {
-        match p {
-            Player::Player1 => &mut self.player1,
-            Player::Player2 => &mut self.player2,
-        }
+impl<B: PlayBoard> Main<B> {
+    fn driver(&mut self, p: Player) -> &mut PlayerDriver<B> {
+        &mut self.drivers[p.index()]
     }
 
     fn get_move(&mut self, p: Player) -> Result<Move, Error> {
         Ok(match self.driver(p) {
             PlayerDriver::Keyboard => {
-                self.display
-                    .get_move(&self.board.clone().into(), &p, &mut self.candidate)?;
+                self.display.get_move(
+                    &self.game.board().clone().into(),
+                    &p,
+                    &mut self.candidate,
+                )?;
                 self.candidate.clone()
             }
             PlayerDriver::RemotePlayer(p) => p.receive()?,
+            PlayerDriver::Agent(agent) => agent.choose(Budget::Default)?,
         })
     }
 
@@ -176,67 +201,235 @@ impl Main {
         match self.driver(p) {
             PlayerDriver::Keyboard => (),
             PlayerDriver::RemotePlayer(p) => p.send(mov)?,
+            PlayerDriver::Agent(agent) => agent.observe(mov)?,
         }
         Ok(())
     }
 }
 
 fn main() -> Result<(), Error> {
-    let opts: Opts = Opts::parse();
+    let mut opts: Opts = Opts::parse();
+    if let Some(Command::Perft(options)) = opts.command {
+        return run_perft(options);
+    }
+    let kinds = match (
+        opts.player1.take(),
+        opts.player2.take(),
+        opts.player3.take(),
+        opts.player4.take(),
+    ) {
+        (Some(player1), Some(player2), Some(player3), Some(player4)) => {
+            vec![player1, player2, player3, player4]
+        }
+        (Some(player1), Some(player2), None, None) => vec![player1, player2],
+        _ => return Err(Error::UnsupportedPlayerCount),
+    };
+    if board_size(opts.position.as_deref(), opts.size)? > BoardV2::MAX_SIZE {
+        play::<BoardV1>(&opts, &kinds)
+    } else {
+        play::<BoardV2>(&opts, &kinds)
+    }
+}
+
+/// The size of the board a game starts on, from `position` if there is one.
+/// `BoardV2` is faster, but only `BoardV1` holds boards above 9x9.
+fn board_size(position: Option<&str>, size: u8) -> Result<u8, Error> {
+    Ok(match position {
+        Some(position) => GameRecord::<BoardV1>::from_fen(position)
+            .map_err(Error::BadPosition)?
+            .board()
+            .size(),
+        None => size,
+    })
+}
+
+/// Reads `position`, or sets up a new game when there isn't one.
+fn start_game<B: Board + Clone>(
+    position: Option<&str>,
+    size: u8,
+    players: u8,
+) -> Result<GameRecord<B>, Error> {
+    Ok(match position {
+        Some(position) => GameRecord::from_fen(position).map_err(Error::BadPosition)?,
+        None => GameRecord::new(
+            B::with_players(size, players).ok_or(Error::UnsupportedBoardSize(size))?,
+        ),
+    })
+}
+
+fn play<B: PlayBoard>(opts: &Opts, kinds: &[PlayerKind]) -> Result<(), Error> {
+    let game = start_game::<B>(opts.position.as_deref(), opts.size, kinds.len() as u8)?;
+    if game.board().num_players() as usize != kinds.len() {
+        return Err(Error::UnsupportedPlayerCount);
+    }
 
     let mut main = Main {
-        player1: opts.player1.clone().try_into()?,
-        player2: opts.player2.clone().try_into()?,
+        drivers: kinds
+            .iter()
+            .map(|kind| PlayerDriver::new(kind.clone(), &game))
+            .collect::<Result<_, _>>()?,
         display: display::Display::new()?,
-        board: BoardV2::empty(),
+        game,
         candidate: Move::MoveTo(0, 0),
     };
 
-    let mut current_player = Player::Player1;
-
     loop {
-        main.display.show(&main.board.clone().into())?;
+        main.display.show(&main.game.board().clone().into())?;
 
         if main.display.check_exit() {
+            let game = main.game.clone();
             drop(main);
             println!("User requested exit.");
-            return Ok(());
+            println!("Position: {}", game.fen());
+            return save_game(opts.save.as_deref(), kinds, game);
         }
+        let current_player = main.game.current_player();
         let candidate = main.get_move(current_player)?;
 
-        if !main.board.is_legal(current_player, &candidate) {
-            panic!(
-                "{:?} tried to play an illegal move {:?}",
-                current_player, candidate
-            );
+        main.game
+            .play(&candidate)
+            .map_err(|e| Error::IllegalMove(current_player, candidate.clone(), e))?;
+
+        for player in main.game.board().players() {
+            if *player != current_player {
+                main.send_move(*player, &candidate)?;
+            }
+        }
+
+        if let Some(winner) = main.game.winner() {
+            let game = main.game.clone();
+            drop(main);
+            println!("{:?} ({}) wins!", winner, kinds[winner.index()]);
+            println!("Position: {}", game.fen());
+            return save_game(opts.save.as_deref(), kinds, game);
+        }
+    }
+}
+
+fn run_perft(options: Perft) -> Result<(), Error> {
+    if board_size(options.position.as_deref(), options.size)? > BoardV2::MAX_SIZE {
+        perft_on::<BoardV1>(options)
+    } else {
+        perft_on::<BoardV2>(options)
+    }
+}
+
+fn perft_on<B: Board + Clone>(options: Perft) -> Result<(), Error> {
+    let mut game = start_game::<B>(options.position.as_deref(), options.size, options.players)?;
+
+    let start = std::time::Instant::now();
+    let nodes = if options.divide {
+        let split = perft::divide(&mut game, options.depth);
+        for (mov, count) in &split {
+            println!("{}: {}", mov, count);
         }
+        println!();
+        split.iter().map(|(_, count)| count).sum()
+    } else {
+        perft::perft(&mut game, options.depth)
+    };
+    let elapsed = start.elapsed();
+    println!("Nodes: {}", nodes);
+    println!(
+        "Time: {:.3}s ({:.0} nodes/s)",
+        elapsed.as_secs_f64(),
+        nodes as f64 / elapsed.as_secs_f64()
+    );
+    Ok(())
+}
 
-        main.board
-            .apply_move(&candidate, current_player)
-            .map_err(|_| Error::InvalidMoveAttempted)?;
+fn save_game<B: Board + Clone + PartialEq>(
+    path: Option<&str>,
+    kinds: &[PlayerKind],
+    game: GameRecord<B>,
+) -> Result<(), Error> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let file = game_file(kinds, game);
+    std::fs::write(path, file.to_string()).map_err(Error::SaveError)
+}
 
-        main.send_move(current_player.other(), &candidate)?;
+/// `kinds` are in player number order, like `Player::index`.
+fn game_file<B: Board>(kinds: &[PlayerKind], game: GameRecord<B>) -> pgn::GameFile<B> {
+    let mut file = pgn::GameFile::new(game);
+    for (i, kind) in kinds.iter().enumerate() {
+        file.set_tag(format!("Player{}", i + 1), kind.to_string());
+    }
+    file.set_tag("Date", today());
+    file
+}
+
+/// Today's UTC date as `yyyy.mm.dd`.
+fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    // Howard Hinnant's days-to-civil algorithm
+    let z = (secs / 86400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{}.{:02}.{:02}", year, month, day)
+}
 
-        if main.board.player_location(Player::Player1).1 == 8
-            || main.board.player_location(Player::Player2).1 == 0
-        {
-            let winner = if main.board.player_location(Player::Player1).1 == 8 {
-                Player::Player1
-            } else {
-                Player::Player2
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_players_by_number() {
+        let kinds = ["keyboard", "greedy-ai", "rubot", "alpha-beta-100"]
+            .iter()
+            .map(|kind| kind.parse().unwrap())
+            .collect::<Vec<PlayerKind>>();
+        let game = GameRecord::new(BoardV2::with_players(9, 4).unwrap());
+        let file = game_file(&kinds, game);
+        assert_eq!(Some("keyboard"), file.tag("Player1"));
+        assert_eq!(Some("greedy-ai"), file.tag("Player2"));
+        assert_eq!(Some("rubot"), file.tag("Player3"));
+        assert_eq!(Some("alpha-beta-100"), file.tag("Player4"));
+    }
+
+    #[test]
+    fn plays_on_boards_above_9x9() {
+        let position = "- - f2,f10 12,12 1 11";
+        assert_eq!(11, board_size(Some(position), 9).unwrap());
+        assert!(board_size(Some("- - f2,f10 12,12 1 13"), 9).is_err());
+        assert!(start_game::<BoardV2>(None, 11, 2).is_err());
+
+        let kinds = ["greedy-ai", "alpha-beta-200"]
+            .iter()
+            .map(|kind| kind.parse().unwrap())
+            .collect::<Vec<PlayerKind>>();
+        let mut game = start_game::<BoardV1>(Some(position), 9, 2).unwrap();
+        let mut drivers = kinds
+            .iter()
+            .map(|kind| PlayerDriver::new(kind.clone(), &game))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        while game.winner().is_none() && game.ply() < 400 {
+            let player = game.current_player();
+            let mov = match &mut drivers[player.index()] {
+                PlayerDriver::Agent(agent) => agent.choose(Budget::Default).unwrap(),
+                _ => unreachable!("both players are bots"),
             };
-            drop(main);
-            println!(
-                "{:?} ({}) wins!",
-                winner,
-                match winner {
-                    Player::Player1 => opts.player1,
-                    Player::Player2 => opts.player2,
-                }
-            );
-            return Ok(());
+            game.play(&mov).unwrap();
+            if let PlayerDriver::Agent(agent) = &mut drivers[player.next(2).index()] {
+                agent.observe(&mov).unwrap();
+            }
         }
+        assert!(game.winner().is_some());
 
-        current_player = current_player.other();
+        let text = game_file(&kinds, game.clone()).to_string();
+        assert!(text.contains("[Size \"11\"]"), "{}", text);
+        let read: pgn::GameFile<BoardV1> = text.parse().unwrap();
+        assert_eq!(&game, read.game());
     }
 }
@@ -0,0 +1,165 @@
+//! A board that picks its representation from its size, for front ends that
+//! only learn the size at run time: `BoardV2` up to 9x9 and `BoardV1` above.
+
+use crate::{
+    bitpacked::BoardV2, v1::BoardV1, AppliedMove, Board, DistanceField, IllegalMove, Move,
+    MoveList, Orientation, Player,
+};
+use alloc::boxed::Box;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum AnyBoard {
+    V2(BoardV2),
+    V1(Box<BoardV1>),
+}
+
+macro_rules! each {
+    ($self:expr, $board:ident => $body:expr) => {
+        match $self {
+            AnyBoard::V2($board) => $body,
+            AnyBoard::V1($board) => $body,
+        }
+    };
+}
+
+impl AnyBoard {
+    /// Who moves next, as far as the board knows. `BoardV1` doesn't track it
+    /// and always says player 1.
+    pub fn side_to_move(&self) -> Player {
+        match self {
+            AnyBoard::V2(board) => board.side_to_move(),
+            AnyBoard::V1(_) => Player::Player1,
+        }
+    }
+}
+
+impl From<BoardV2> for AnyBoard {
+    fn from(board: BoardV2) -> Self {
+        AnyBoard::V2(board)
+    }
+}
+
+impl From<BoardV1> for AnyBoard {
+    fn from(board: BoardV1) -> Self {
+        AnyBoard::V1(Box::new(board))
+    }
+}
+
+impl From<AnyBoard> for BoardV1 {
+    fn from(board: AnyBoard) -> Self {
+        match board {
+            AnyBoard::V2(board) => board.into(),
+            AnyBoard::V1(board) => *board,
+        }
+    }
+}
+
+impl Board for AnyBoard {
+    fn with_players(size: u8, players: u8) -> Option<Self> {
+        if size <= BoardV2::MAX_SIZE {
+            BoardV2::with_players(size, players).map(AnyBoard::V2)
+        } else {
+            BoardV1::with_players(size, players).map(AnyBoard::from)
+        }
+    }
+
+    fn size(&self) -> u8 {
+        each!(self, board => board.size())
+    }
+
+    fn num_players(&self) -> u8 {
+        each!(self, board => board.num_players())
+    }
+
+    fn available_walls(&self, player: Player) -> u8 {
+        each!(self, board => board.available_walls(player))
+    }
+
+    fn set_available_walls(&mut self, player: Player, walls: u8) {
+        each!(self, board => board.set_available_walls(player, walls))
+    }
+
+    fn set_side_to_move(&mut self, player: Player) {
+        each!(self, board => board.set_side_to_move(player))
+    }
+
+    fn position_key(&self, to_move: Player) -> u64 {
+        each!(self, board => board.position_key(to_move))
+    }
+
+    fn add_wall(
+        &mut self,
+        player: Player,
+        location: (u8, u8),
+        orientation: Orientation,
+    ) -> Result<(), IllegalMove> {
+        each!(self, board => board.add_wall(player, location, orientation))
+    }
+
+    fn move_token(&mut self, player: Player, new_location: (u8, u8)) -> Result<(), IllegalMove> {
+        each!(self, board => board.move_token(player, new_location))
+    }
+
+    fn is_probably_legal(&self, player: Player, candidate_move: &Move) -> bool {
+        each!(self, board => board.is_probably_legal(player, candidate_move))
+    }
+
+    fn check_move(&self, player: Player, candidate_move: &Move) -> Result<(), IllegalMove> {
+        each!(self, board => board.check_move(player, candidate_move))
+    }
+
+    fn get_wall_state(&self, location: (u8, u8)) -> Option<Orientation> {
+        each!(self, board => board.get_wall_state(location))
+    }
+
+    fn remove_wall(&mut self, player: Player, location: (u8, u8), orientation: Orientation) {
+        each!(self, board => board.remove_wall(player, location, orientation))
+    }
+
+    fn make_move(&mut self, candidate: &Move, player: Player) -> Result<AppliedMove, IllegalMove> {
+        each!(self, board => board.make_move(candidate, player))
+    }
+
+    fn undo_move(&mut self, applied: &AppliedMove) {
+        each!(self, board => board.undo_move(applied))
+    }
+
+    fn player_location(&self, player: Player) -> (u8, u8) {
+        each!(self, board => board.player_location(player))
+    }
+
+    fn distance_to_goal(&self, player: Player) -> Option<u8> {
+        each!(self, board => board.distance_to_goal(player))
+    }
+
+    fn distance_field(&self, player: Player) -> DistanceField {
+        each!(self, board => board.distance_field(player))
+    }
+
+    fn is_passible(&self, location: (u8, u8), new_location: (u8, u8)) -> bool {
+        each!(self, board => board.is_passible(location, new_location))
+    }
+
+    fn generate_moves(&self, player: Player, moves: &mut MoveList) {
+        each!(self, board => board.generate_moves(player, moves))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{perft, GameRecord};
+
+    #[test]
+    fn picks_the_board_by_size() {
+        assert!(matches!(AnyBoard::empty(), AnyBoard::V2(_)));
+        assert!(matches!(AnyBoard::with_size(11).unwrap(), AnyBoard::V1(_)));
+        assert_eq!(None, AnyBoard::with_size(13));
+
+        let mut game = GameRecord::<AnyBoard>::from_fen("- - f2,f10 12,12 1 11").unwrap();
+        assert_eq!(204, perft::perft(&mut game, 1));
+        let mut game = GameRecord::<AnyBoard>::from_fen("- - c1,c5 2,2 1 5").unwrap();
+        let mut packed = GameRecord::<BoardV2>::from_fen("- - c1,c5 2,2 1 5").unwrap();
+        assert_eq!(perft::perft(&mut packed, 3), perft::perft(&mut game, 3));
+    }
+}
@@ -1,6 +1,9 @@
-use crate::{Board, Direction, Move, Orientation, Player};
-use fxhash::FxHasher;
-use std::{
+use crate::{
+    is_valid_player_count, is_valid_size, symmetry::Symmetry, walls_per_player, AppliedMove, Board,
+    Direction, DistanceField, IllegalMove, Move, MoveList, Orientation, Player, MAX_SIZE,
+};
+use alloc::{format, string::String};
+use core::{
     convert::{TryFrom, TryInto},
     hash::{Hash, Hasher},
     num::NonZeroU8,
@@ -10,16 +13,97 @@ use std::{
 pub struct BoardV2 {
     horizontal: u64,
     vertical: u64,
-    player1_pos: Position,
-    player2_pos: Position,
-    player1_walls: u8,
-    player2_walls: u8,
+    positions: [Position; 4],
+    walls: [u8; 4],
+    size: u8,
+    players: u8,
+    zobrist: u64,
+    to_move: Player,
+    /// Cells, as bits `y * 9 + x`, whose right and lower edges can be crossed.
+    open_right: u128,
+    open_down: u128,
 }
 #[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
 pub struct Position(NonZeroU8);
 
-impl std::fmt::Display for Position {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+const WALL_SLOTS: usize = (MAX_SIZE as usize - 1) * (MAX_SIZE as usize - 1);
+const SQUARES: usize = MAX_SIZE as usize * MAX_SIZE as usize;
+
+/// Random keys for Zobrist hashing, one per wall slot, pawn square, walls left
+/// count and side to move, laid out for the largest board so every board
+/// type can share them.
+struct ZobristKeys {
+    horizontal: [u64; WALL_SLOTS],
+    vertical: [u64; WALL_SLOTS],
+    pawns: [[u64; SQUARES]; 4],
+    walls: [[u64; 256]; 4],
+    side: [u64; 4],
+}
+
+static ZOBRIST: ZobristKeys = {
+    // splitmix64, so the keys are fixed across builds
+    const fn next(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    let mut state = 0x5175_6f72_6964_6f72;
+    let mut keys = ZobristKeys {
+        horizontal: [0; WALL_SLOTS],
+        vertical: [0; WALL_SLOTS],
+        pawns: [[0; SQUARES]; 4],
+        walls: [[0; 256]; 4],
+        side: [0; 4],
+    };
+    let mut i = 0;
+    while i < WALL_SLOTS {
+        keys.horizontal[i] = next(&mut state);
+        keys.vertical[i] = next(&mut state);
+        i += 1;
+    }
+    let mut player = 0;
+    while player < 4 {
+        let mut i = 0;
+        while i < SQUARES {
+            keys.pawns[player][i] = next(&mut state);
+            i += 1;
+        }
+        let mut i = 0;
+        while i < 256 {
+            keys.walls[player][i] = next(&mut state);
+            i += 1;
+        }
+        keys.side[player] = next(&mut state);
+        player += 1;
+    }
+    keys
+};
+
+pub(crate) fn wall_key((x, y): (u8, u8), orientation: Orientation) -> u64 {
+    let slot = y as usize * (MAX_SIZE as usize - 1) + x as usize;
+    match orientation {
+        Orientation::Horizontal => ZOBRIST.horizontal[slot],
+        Orientation::Vertical => ZOBRIST.vertical[slot],
+    }
+}
+
+pub(crate) fn pawn_key(player: Player, (x, y): (u8, u8)) -> u64 {
+    ZOBRIST.pawns[player.index()][y as usize * MAX_SIZE as usize + x as usize]
+}
+
+pub(crate) fn walls_key(player: Player, walls: u8) -> u64 {
+    ZOBRIST.walls[player.index()][walls as usize]
+}
+
+pub(crate) fn side_key(player: Player) -> u64 {
+    ZOBRIST.side[player.index()]
+}
+
+impl core::fmt::Display for Position {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
@@ -31,22 +115,57 @@ pub enum State {
 }
 
 impl Board for BoardV2 {
-    fn empty() -> Self {
-        Self {
+    fn with_players(size: u8, players: u8) -> Option<Self> {
+        if !is_valid_size(size) || size > BoardV2::MAX_SIZE || !is_valid_player_count(players) {
+            return None;
+        }
+        let mut board = Self {
             horizontal: 0,
             vertical: 0,
-            player1_pos: (4, 0).try_into().unwrap(),
-            player2_pos: (4, 8).try_into().unwrap(),
-            player1_walls: 10,
-            player2_walls: 10,
-        }
+            positions: [
+                Player::Player1.start(size).try_into().ok()?,
+                Player::Player2.start(size).try_into().ok()?,
+                Player::Player3.start(size).try_into().ok()?,
+                Player::Player4.start(size).try_into().ok()?,
+            ],
+            walls: [walls_per_player(size, players); 4],
+            size,
+            players,
+            zobrist: 0,
+            to_move: Player::Player1,
+            open_right: 0,
+            open_down: 0,
+        };
+        board.update_passability();
+        board.set_side_to_move(Player::Player1);
+        Some(board)
+    }
+
+    fn set_side_to_move(&mut self, player: Player) {
+        self.to_move = player;
+        self.zobrist = self.compute_zobrist(player);
+    }
+
+    /// The key kept up to date in `zobrist`, for another side to move.
+    fn position_key(&self, to_move: Player) -> u64 {
+        self.zobrist ^ side_key(self.to_move) ^ side_key(to_move)
+    }
+
+    fn size(&self) -> u8 {
+        self.size
+    }
+
+    fn num_players(&self) -> u8 {
+        self.players
     }
 
     fn available_walls(&self, player: Player) -> u8 {
-        match player {
-            Player::Player1 => self.player1_walls,
-            Player::Player2 => self.player2_walls,
-        }
+        self.walls[player.index()]
+    }
+
+    fn set_available_walls(&mut self, player: Player, walls: u8) {
+        self.zobrist ^= walls_key(player, self.walls[player.index()]) ^ walls_key(player, walls);
+        self.walls[player.index()] = walls;
     }
 
     fn add_wall(
@@ -54,37 +173,72 @@ impl Board for BoardV2 {
         player: Player,
         location: (u8, u8),
         orientation: crate::Orientation,
-    ) -> Result<(), ()> {
-        BoardV2::bit_mask(location)
-            .and_then(|mask| {
-                if (self.vertical | self.horizontal) & mask != 0 {
-                    return None;
-                }
-                let bitset = match orientation {
-                    crate::Orientation::Horizontal => &mut self.horizontal,
-                    crate::Orientation::Vertical => &mut self.vertical,
-                };
-                *bitset |= mask;
-                match player {
-                    Player::Player1 => self.player1_walls -= 1,
-                    Player::Player2 => self.player2_walls -= 1,
-                }
-                Some(())
-            })
-            .ok_or(())
+    ) -> Result<(), IllegalMove> {
+        let mask = self.bit_mask(location).ok_or(IllegalMove::OutOfBounds)?;
+        if self.available_walls(player) == 0 {
+            return Err(IllegalMove::NoWallsLeft);
+        }
+        if self.open_wall_slots(orientation) & mask == 0 {
+            return Err(IllegalMove::WallOverlaps);
+        }
+        let bitset = match orientation {
+            crate::Orientation::Horizontal => &mut self.horizontal,
+            crate::Orientation::Vertical => &mut self.vertical,
+        };
+        *bitset |= mask;
+        let (right, down) = wall_edges(location, orientation);
+        self.open_right &= !right;
+        self.open_down &= !down;
+        self.toggle_wall(location, orientation);
+        self.set_available_walls(player, self.available_walls(player) - 1);
+        self.pass_turn(player.next(self.players));
+        Ok(())
+    }
+
+    fn remove_wall(&mut self, player: Player, location: (u8, u8), orientation: Orientation) {
+        if let Some(mask) = self.bit_mask(location) {
+            match orientation {
+                Orientation::Horizontal => self.horizontal &= !mask,
+                Orientation::Vertical => self.vertical &= !mask,
+            }
+            self.update_passability();
+            self.toggle_wall(location, orientation);
+            self.set_available_walls(player, self.available_walls(player) + 1);
+            self.pass_turn(player);
+        }
+    }
+
+    fn move_token(&mut self, player: Player, new_location: (u8, u8)) -> Result<(), IllegalMove> {
+        self.place_pawn(player, new_location)?;
+        self.pass_turn(player.next(self.players));
+        Ok(())
     }
 
-    fn move_token(&mut self, player: Player, new_location: (u8, u8)) -> Result<(), ()> {
-        self.set_player_location(player, new_location)
+    /// Puts everything back as it was before `applied.player` moved,
+    /// including whose turn it is.
+    fn undo_move(&mut self, applied: &AppliedMove) {
+        match applied.mov {
+            Move::AddWall {
+                location,
+                orientation,
+            } => self.remove_wall(applied.player, location, orientation),
+            Move::MoveTo(..) => {
+                self.place_pawn(applied.player, applied.from)
+                    .expect("pawn returns to a square it came from");
+                self.pass_turn(applied.player);
+            }
+        }
     }
 
     fn get_wall_state(&self, location: (u8, u8)) -> Option<Orientation> {
-        if BoardV2::bit_mask(location)
+        if self
+            .bit_mask(location)
             .map(|x| x & self.horizontal != 0)
             .unwrap_or(false)
         {
             Some(Orientation::Horizontal)
-        } else if BoardV2::bit_mask(location)
+        } else if self
+            .bit_mask(location)
             .map(|x| x & self.vertical != 0)
             .unwrap_or(false)
         {
@@ -100,44 +254,33 @@ impl Board for BoardV2 {
                 location,
                 orientation,
             } => {
-                if match player {
-                    Player::Player1 => self.player1_walls,
-                    Player::Player2 => self.player2_walls,
-                } == 0
-                {
+                if self.available_walls(player) == 0 {
                     return false;
                 }
 
-                if BoardV2::bit_mask(*location).is_none() {
+                if self.bit_mask(*location).is_none() {
                     return false;
                 }
 
-                fn directions_to_mask(poses: impl Iterator<Item = Option<(u8, u8)>>) -> u64 {
+                let directions_to_mask = |poses: &[Option<(u8, u8)>]| -> u64 {
                     poses
-                        .filter_map(|x| x)
-                        .filter_map(|x| BoardV2::bit_mask(x))
+                        .iter()
+                        .flatten()
+                        .filter_map(|x| self.bit_mask(*x))
                         .fold(0, |acc, x| acc | x)
-                }
+                };
 
-                let h_mask = directions_to_mask(
-                    [
-                        Direction::Left.shift(*location),
-                        Some(*location),
-                        Direction::Right.shift(*location),
-                    ]
-                    .iter()
-                    .map(|x| *x),
-                );
+                let h_mask = directions_to_mask(&[
+                    Direction::Left.shift(*location, self.size),
+                    Some(*location),
+                    Direction::Right.shift(*location, self.size),
+                ]);
 
-                let v_mask = directions_to_mask(
-                    [
-                        Direction::Up.shift(*location),
-                        Some(*location),
-                        Direction::Down.shift(*location),
-                    ]
-                    .iter()
-                    .map(|x| *x),
-                );
+                let v_mask = directions_to_mask(&[
+                    Direction::Up.shift(*location, self.size),
+                    Some(*location),
+                    Direction::Down.shift(*location, self.size),
+                ]);
 
                 let unfilled = match orientation {
                     Orientation::Vertical => (self.vertical & v_mask) == 0,
@@ -145,208 +288,559 @@ impl Board for BoardV2 {
                 };
                 unfilled
             }
-            Move::MoveTo(x, y) => self.is_passible(self.player_location(player), (*x, *y)),
+            Move::MoveTo(x, y) => self.pawn_moves(player).contains(&(*x, *y)),
         }
     }
 
-    fn is_legal(&self, player: Player, candidate_move: &Move) -> bool {
+    fn check_move(&self, player: Player, candidate_move: &Move) -> Result<(), IllegalMove> {
         match candidate_move {
             Move::AddWall {
                 location,
                 orientation,
             } => {
-                if match player {
-                    Player::Player1 => self.player1_walls,
-                    Player::Player2 => self.player2_walls,
-                } == 0
-                {
-                    return false;
+                let mask = self.bit_mask(*location).ok_or(IllegalMove::OutOfBounds)?;
+                if self.available_walls(player) == 0 {
+                    return Err(IllegalMove::NoWallsLeft);
                 }
-
-                if BoardV2::bit_mask(*location).is_none() {
-                    return false;
+                if self.open_wall_slots(*orientation) & mask == 0 {
+                    return Err(IllegalMove::WallOverlaps);
                 }
 
-                fn directions_to_mask(poses: impl Iterator<Item = Option<(u8, u8)>>) -> u64 {
-                    poses
-                        .filter_map(|x| x)
-                        .filter_map(|x| BoardV2::bit_mask(x))
-                        .fold(0, |acc, x| acc | x)
+                if self.wall_blocks_path(*location, *orientation) {
+                    Err(IllegalMove::WallBlocksPath)
+                } else {
+                    Ok(())
                 }
-
-                let h_mask = directions_to_mask(
-                    [
-                        Direction::Left.shift(*location),
-                        Some(*location),
-                        Direction::Right.shift(*location),
-                    ]
-                    .iter()
-                    .map(|x| *x),
-                );
-
-                let v_mask = directions_to_mask(
-                    [
-                        Direction::Up.shift(*location),
-                        Some(*location),
-                        Direction::Down.shift(*location),
-                    ]
-                    .iter()
-                    .map(|x| *x),
-                );
-
-                let unfilled = match orientation {
-                    Orientation::Vertical => (self.vertical & v_mask) == 0,
-                    Orientation::Horizontal => (self.horizontal & h_mask) == 0,
-                };
-
-                let mut hypo = self.clone();
-                let added_wall = hypo.add_wall(player, *location, *orientation).is_ok();
-                let p1_can_exit = hypo.distance_to_goal(Player::Player1).is_some();
-                let p2_can_exit = hypo.distance_to_goal(Player::Player2).is_some();
-
-                added_wall && unfilled && p1_can_exit && p2_can_exit
             }
             Move::MoveTo(nx, ny) => {
-                let my_location = self.player_location(player);
-                let other_location = self.player_location(player.other());
-                if (*nx, *ny) == other_location || (*nx, *ny) == my_location {
-                    false
-                } else if self.is_passible(my_location, (*nx, *ny)) {
-                    true
-                } else if self.is_passible(my_location, other_location) {
-                    let (tx, ty) = (
-                        other_location.0 - my_location.0,
-                        other_location.1 - my_location.1,
-                    );
-                    let following_trajectory = (other_location.0 + tx, other_location.1 + ty);
-                    if self.is_passible(other_location, following_trajectory) {
-                        following_trajectory == (*nx, *ny)
-                    } else {
-                        self.is_passible(other_location, (*nx, *ny))
-                    }
+                if *nx >= self.size || *ny >= self.size {
+                    return Err(IllegalMove::OutOfBounds);
+                }
+                if self.pawn_moves(player).contains(&(*nx, *ny)) {
+                    Ok(())
                 } else {
-                    false
+                    Err(IllegalMove::IllegalPawnMove)
                 }
             }
         }
     }
 
     fn player_location(&self, player: Player) -> (u8, u8) {
-        match player {
-            super::Player::Player1 => self.player1_pos,
-            super::Player::Player2 => self.player2_pos,
-        }
-        .into()
+        self.positions[player.index()].into()
     }
 
     fn is_passible(&self, (x, y): (u8, u8), (nx, ny): (u8, u8)) -> bool {
+        if x >= self.size || y >= self.size {
+            return false;
+        }
         match (nx as i8 - x as i8, ny as i8 - y as i8) {
-            (1, 0) => x < 8 && self.is_passible_right((x, y)),
-            (0, 1) => y < 8 && self.is_passible_down((x, y)),
-            (0, -1) => y > 0 && self.is_passible_down((x, y - 1)),
-            (-1, 0) => x > 0 && self.is_passible_right((x - 1, y)),
+            (1, 0) => self.open_right & cell((x, y)) != 0,
+            (0, 1) => self.open_down & cell((x, y)) != 0,
+            (0, -1) => y > 0 && self.open_down & cell((x, y - 1)) != 0,
+            (-1, 0) => x > 0 && self.open_right & cell((x - 1, y)) != 0,
             _ => false,
         }
     }
+
+    fn distance_to_goal(&self, player: Player) -> Option<u8> {
+        self.distance_with(player, self.open_right, self.open_down)
+    }
+
+    fn distance_field(&self, player: Player) -> DistanceField {
+        let mut field = DistanceField::unreachable(self.size);
+        let mut reached = self.goal_cells(player);
+        let mut layer = reached;
+        let mut distance = 0;
+        while layer != 0 {
+            let mut cells = layer;
+            while cells != 0 {
+                let index = cells.trailing_zeros() as u8;
+                cells &= cells - 1;
+                field.set((index % 9, index / 9), distance);
+            }
+            let next = spread(reached, self.open_right, self.open_down);
+            layer = next & !reached;
+            reached = next;
+            distance += 1;
+        }
+        field
+    }
+
+    fn generate_moves(&self, player: Player, moves: &mut MoveList) {
+        self.generate_pawn_moves(player, moves);
+        if self.available_walls(player) == 0 {
+            return;
+        }
+        for orientation in [Orientation::Horizontal, Orientation::Vertical].iter() {
+            let slots = self.open_wall_slots(*orientation) & !self.blocking_walls(*orientation);
+            for y in 0..self.size - 1 {
+                for x in 0..self.size - 1 {
+                    if slots & 1 << (x * 8 + y) != 0 {
+                        moves.push(Move::AddWall {
+                            location: (x, y),
+                            orientation: *orientation,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn cell((x, y): (u8, u8)) -> u128 {
+    1 << (y as u32 * 9 + x as u32)
+}
+
+/// The cells whose right and lower edges a wall at `(x, y)` closes.
+fn wall_edges((x, y): (u8, u8), orientation: Orientation) -> (u128, u128) {
+    match orientation {
+        Orientation::Horizontal => (0, cell((x, y)) | cell((x + 1, y))),
+        Orientation::Vertical => (cell((x, y)) | cell((x, y + 1)), 0),
+    }
+}
+
+/// The wall slots that would close one of `edges`, given as cells. A wall
+/// closes the edge of its own cell and of the next cell over, `along`.
+fn slots_closing(mut edges: u128, along: (u8, u8)) -> u64 {
+    let mut slots = 0;
+    while edges != 0 {
+        let index = edges.trailing_zeros() as u8;
+        edges &= edges - 1;
+        let (x, y) = (index % 9, index / 9);
+        for (x, y) in [
+            (Some(x), Some(y)),
+            (x.checked_sub(along.0), y.checked_sub(along.1)),
+        ]
+        .iter()
+        {
+            if let (Some(x), Some(y)) = (x, y) {
+                if *x < 8 && *y < 8 {
+                    slots |= 1 << (x * 8 + y);
+                }
+            }
+        }
+    }
+    slots
+}
+
+/// `cells` plus every cell one open step away from them.
+fn spread(cells: u128, open_right: u128, open_down: u128) -> u128 {
+    cells
+        | (cells & open_right) << 1
+        | (cells >> 1) & open_right
+        | (cells & open_down) << 9
+        | (cells >> 9) & open_down
 }
 
 impl BoardV2 {
-    pub fn fx_hash(&self, hasher: &mut FxHasher) {
+    /// Walls are stored in 8x8 bitsets, so this representation stops at 9x9.
+    pub const MAX_SIZE: u8 = 9;
+
+    pub fn can_reach_goal(&self, player: Player) -> bool {
+        self.distance_to_goal(player).is_some()
+    }
+
+    /// Wall slots, as bits `x * 8 + y`, where a wall of `orientation` would
+    /// not overlap one already on the board.
+    pub fn open_wall_slots(&self, orientation: Orientation) -> u64 {
+        const FIRST_ROW: u64 = 0x0101_0101_0101_0101;
+        const LAST_ROW: u64 = 0x8080_8080_8080_8080;
+
+        let column = (1 << (self.size - 1)) - 1;
+        let on_board = (0..self.size - 1).fold(0, |slots, x| slots | column << (x * 8));
+        let taken = self.horizontal | self.vertical;
+        let blocked = match orientation {
+            Orientation::Horizontal => taken | self.horizontal << 8 | self.horizontal >> 8,
+            Orientation::Vertical => {
+                taken | (self.vertical << 1) & !FIRST_ROW | (self.vertical >> 1) & !LAST_ROW
+            }
+        };
+        on_board & !blocked
+    }
+
+    /// Open wall slots, as bits `x * 8 + y`, where a wall of `orientation`
+    /// would leave some player without a way to their goal. It doesn't matter
+    /// who places the wall. A wall can only cut a player off if it crosses
+    /// their shortest path and closes off a region, touching the border or
+    /// other walls at two points, so only those slots get a full search.
+    pub fn blocking_walls(&self, orientation: Orientation) -> u64 {
+        let open = self.open_wall_slots(orientation);
+        let mut crossing = 0;
+        for player in self.players() {
+            let (right, down) = match self.shortest_path_edges(*player) {
+                Some(edges) => edges,
+                None => return open,
+            };
+            crossing |= match orientation {
+                Orientation::Horizontal => slots_closing(down, (1, 0)),
+                Orientation::Vertical => slots_closing(right, (0, 1)),
+            };
+        }
+
+        let mut candidates = open & crossing & self.anchored_slots(orientation);
+        let mut blocking = 0;
+        while candidates != 0 {
+            let bit = candidates.trailing_zeros() as u8;
+            candidates &= candidates - 1;
+            if self.wall_blocks_path((bit / 8, bit % 8), orientation) {
+                blocking |= 1 << bit;
+            }
+        }
+        blocking
+    }
+
+    /// The edges one shortest path for `player` crosses, as the cells whose
+    /// right and lower edges they are.
+    fn shortest_path_edges(&self, player: Player) -> Option<(u128, u128)> {
+        let goal = self.goal_cells(player);
+        let mut layers = [0; 81];
+        layers[0] = cell(self.player_location(player));
+        let mut distance = 0;
+        while layers[distance] & goal == 0 {
+            let next = spread(layers[distance], self.open_right, self.open_down);
+            if next == layers[distance] {
+                return None;
+            }
+            distance += 1;
+            layers[distance] = next;
+        }
+
+        // walk back from a goal cell, one layer at a time
+        let (mut right, mut down) = (0, 0);
+        let mut at = layers[distance] & goal;
+        at &= at.wrapping_neg();
+        for earlier in layers[..distance].iter().rev() {
+            if (at >> 1) & self.open_right & earlier != 0 {
+                at >>= 1;
+                right |= at;
+            } else if at & self.open_right != 0 && (at << 1) & earlier != 0 {
+                right |= at;
+                at <<= 1;
+            } else if (at >> 9) & self.open_down & earlier != 0 {
+                at >>= 9;
+                down |= at;
+            } else {
+                down |= at;
+                at <<= 9;
+            }
+        }
+        Some((right, down))
+    }
+
+    /// Slots where a wall of `orientation` would touch the border or other
+    /// walls at two or more of its ends and middle.
+    fn anchored_slots(&self, orientation: Orientation) -> u64 {
+        let size = self.size;
+        // the corners between cells, as bits `y * 10 + x`
+        let corner = |x: u8, y: u8| 1u128 << (y as u32 * 10 + x as u32);
+        let points = |(x, y): (u8, u8), orientation| match orientation {
+            Orientation::Horizontal => {
+                [corner(x, y + 1), corner(x + 1, y + 1), corner(x + 2, y + 1)]
+            }
+            Orientation::Vertical => [corner(x + 1, y), corner(x + 1, y + 1), corner(x + 1, y + 2)],
+        };
+
+        let mut solid = 0;
+        for i in 0..=size {
+            solid |= corner(i, 0) | corner(i, size) | corner(0, i) | corner(size, i);
+        }
+        let mut walls = self.horizontal | self.vertical;
+        while walls != 0 {
+            let bit = walls.trailing_zeros() as u8;
+            walls &= walls - 1;
+            let placed = if self.horizontal & 1 << bit != 0 {
+                Orientation::Horizontal
+            } else {
+                Orientation::Vertical
+            };
+            for point in points((bit / 8, bit % 8), placed).iter() {
+                solid |= point;
+            }
+        }
+
+        let mut slots = 0;
+        for x in 0..size - 1 {
+            for y in 0..size - 1 {
+                let touching = points((x, y), orientation)
+                    .iter()
+                    .filter(|point| solid & **point != 0)
+                    .count();
+                if touching >= 2 {
+                    slots |= 1 << (x * 8 + y);
+                }
+            }
+        }
+        slots
+    }
+
+    /// Whether a wall here would leave some player without a way to their goal.
+    fn wall_blocks_path(&self, location: (u8, u8), orientation: Orientation) -> bool {
+        let (right, down) = wall_edges(location, orientation);
+        let (open_right, open_down) = (self.open_right & !right, self.open_down & !down);
+        self.players()
+            .iter()
+            .any(|player| self.distance_with(*player, open_right, open_down).is_none())
+    }
+
+    /// Breadth first search over the whole frontier at once.
+    fn distance_with(&self, player: Player, open_right: u128, open_down: u128) -> Option<u8> {
+        let goal = self.goal_cells(player);
+        let mut reached = cell(self.player_location(player));
+        let mut distance = 0;
+        while reached & goal == 0 {
+            let next = spread(reached, open_right, open_down);
+            if next == reached {
+                return None;
+            }
+            reached = next;
+            distance += 1;
+        }
+        Some(distance)
+    }
+
+    fn goal_cells(&self, player: Player) -> u128 {
+        let last = self.size - 1;
+        (0..self.size)
+            .map(|i| match player {
+                Player::Player1 => cell((i, last)),
+                Player::Player2 => cell((i, 0)),
+                Player::Player3 => cell((0, i)),
+                Player::Player4 => cell((last, i)),
+            })
+            .fold(0, |cells, c| cells | c)
+    }
+
+    /// Rebuilds the passability masks from the wall bitsets.
+    fn update_passability(&mut self) {
+        let last = self.size - 1;
+        self.open_right = 0;
+        self.open_down = 0;
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if x < last {
+                    self.open_right |= cell((x, y));
+                }
+                if y < last {
+                    self.open_down |= cell((x, y));
+                }
+            }
+        }
+        let mut walls = self.horizontal | self.vertical;
+        while walls != 0 {
+            let bit = walls.trailing_zeros() as u8;
+            walls &= walls - 1;
+            let orientation = if self.horizontal & 1 << bit != 0 {
+                Orientation::Horizontal
+            } else {
+                Orientation::Vertical
+            };
+            let (right, down) = wall_edges((bit / 8, bit % 8), orientation);
+            self.open_right &= !right;
+            self.open_down &= !down;
+        }
+    }
+
+    /// A hash of the position and side to move, kept up to date as moves are
+    /// made and undone. The side to move starts as player 1 and passes on
+    /// with each move; use `set_side_to_move` after setting up a position.
+    pub fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
+
+    /// The side to move: whoever follows the last player to move, or as set
+    /// with `set_side_to_move`.
+    pub fn side_to_move(&self) -> Player {
+        self.to_move
+    }
+
+    fn compute_zobrist(&self, side_to_move: Player) -> u64 {
+        let mut key = side_key(side_to_move);
+        for bit in 0..64 {
+            let location = (bit / 8, bit % 8);
+            if self.horizontal & 1 << bit != 0 {
+                key ^= wall_key(location, Orientation::Horizontal);
+            }
+            if self.vertical & 1 << bit != 0 {
+                key ^= wall_key(location, Orientation::Vertical);
+            }
+        }
+        for player in self.players() {
+            key ^= pawn_key(*player, self.player_location(*player));
+            key ^= walls_key(*player, self.available_walls(*player));
+        }
+        key
+    }
+
+    fn toggle_wall(&mut self, location: (u8, u8), orientation: Orientation) {
+        if self.bit_idx(location).is_some() {
+            self.zobrist ^= wall_key(location, orientation);
+        }
+    }
+
+    /// Hands the turn to `player`, whoever had it before.
+    fn pass_turn(&mut self, player: Player) {
+        self.zobrist ^= side_key(self.to_move) ^ side_key(player);
+        self.to_move = player;
+    }
+
+    /// Moves a pawn, keeping the Zobrist key up to date.
+    fn place_pawn(&mut self, player: Player, location: (u8, u8)) -> Result<(), IllegalMove> {
+        let from = self.player_location(player);
+        self.set_player_location(player, location)?;
+        self.zobrist ^= pawn_key(player, from) ^ pawn_key(player, location);
+        Ok(())
+    }
+
+    /// Feeds `hasher` only what tells positions apart, for fast hashers
+    /// like `FxHasher` that work a word at a time.
+    pub fn fx_hash<H: Hasher>(&self, hasher: &mut H) {
         hasher.write_u64(self.horizontal);
         hasher.write_u64(self.vertical);
-        hasher.write_u8(self.player1_pos.0.into());
-        hasher.write_u8(self.player2_pos.0.into());
-        hasher.write_u8(self.player2_walls);
-        hasher.write_u8(self.player2_walls);
+        for player in self.players() {
+            hasher.write_u8(self.positions[player.index()].0.into());
+        }
+        hasher.write_u8(self.walls[0]);
+        hasher.write_u8(self.walls[1]);
+        if self.players == 4 {
+            hasher.write_u8(self.walls[2]);
+            hasher.write_u8(self.walls[3]);
+        }
     }
 
+    /// Turns the board upside down, swapping players 1 and 2.
     pub fn flip(&self) -> BoardV2 {
-        fn flip_bytes(n: u64) -> u64 {
-            let a = (n >> 56) & 0xff;
-            let b = (n >> 48) & 0xff;
-            let c = (n >> 40) & 0xff;
-            let d = (n >> 32) & 0xff;
-            let e = (n >> 24) & 0xff;
-            let f = (n >> 16) & 0xff;
-            let g = (n >> 8) & 0xff;
-            let h = (n >> 0) & 0xff;
+        self.transformed(Symmetry::Flip)
+    }
 
-            (a << 0)
-                | (b << 8)
-                | (c << 16)
-                | (d << 24)
-                | (e << 32)
-                | (f << 40)
-                | (g << 48) << (h << 56)
-        }
+    /// Mirrors the board left to right, swapping players 3 and 4.
+    pub fn mirror(&self) -> BoardV2 {
+        self.transformed(Symmetry::Mirror)
+    }
 
-        fn flip_player(l: Position) -> Position {
-            let (x, y) = l.into();
-            (x, 8 - y).try_into().unwrap()
-        }
+    /// This position seen through `symmetry`, side to move included.
+    pub fn transformed(&self, symmetry: Symmetry) -> BoardV2 {
+        // wall bits are indexed x * 8 + y, so each byte is one column
+        let unused = BoardV2::MAX_SIZE - self.size;
+        let transform = |mut bits: u64| {
+            if symmetry.mirrors() {
+                bits = bits.swap_bytes() >> (8 * unused);
+            }
+            if symmetry.flips() {
+                bits = bits.reverse_bits().swap_bytes() >> unused;
+            }
+            bits
+        };
 
-        BoardV2 {
-            horizontal: flip_bytes(self.horizontal),
-            vertical: flip_bytes(self.vertical),
-            player1_pos: flip_player(self.player2_pos),
-            player2_pos: flip_player(self.player1_pos),
-            player1_walls: self.player2_walls,
-            player2_walls: self.player1_walls,
+        let mut board = self.clone();
+        board.horizontal = transform(self.horizontal);
+        board.vertical = transform(self.vertical);
+        board.update_passability();
+        for player in Player::ALL.iter() {
+            let (from, to) = (player.index(), symmetry.player(*player).index());
+            board.positions[to] = symmetry
+                .square(self.positions[from].into(), self.size)
+                .try_into()
+                .unwrap();
+            board.walls[to] = self.walls[from];
         }
+        board.set_side_to_move(symmetry.player(self.side_to_move()));
+        board
+    }
+
+    /// The symmetric twin with the smallest Zobrist key, so positions that
+    /// only differ by a symmetry share one representative, along with the
+    /// symmetry that produced it.
+    pub fn canonical(&self) -> (BoardV2, Symmetry) {
+        Symmetry::for_players(self.players)
+            .iter()
+            .map(|symmetry| (self.transformed(*symmetry), *symmetry))
+            .min_by_key(|(board, _)| board.zobrist())
+            .unwrap()
     }
 
     pub fn from_repr_string(repr: &str) -> Option<BoardV2> {
         let mut bits = repr.split_ascii_whitespace();
 
-        Some(BoardV2 {
-            horizontal: bits.next()?.parse().ok()?,
-            vertical: bits.next()?.parse().ok()?,
-            player1_pos: Position::new(bits.next()?.parse::<u8>().ok()? - 1)?,
-            player2_pos: Position::new(bits.next()?.parse::<u8>().ok()? - 1)?,
-            player1_walls: bits.next()?.parse().ok()?,
-            player2_walls: bits.next()?.parse().ok()?,
-        })
+        let horizontal = bits.next()?.parse().ok()?;
+        let vertical = bits.next()?.parse().ok()?;
+        let player1_pos = Position::new(bits.next()?.parse::<u8>().ok()?.checked_sub(1)?)?;
+        let player2_pos = Position::new(bits.next()?.parse::<u8>().ok()?.checked_sub(1)?)?;
+        let player1_walls = bits.next()?.parse().ok()?;
+        let player2_walls = bits.next()?.parse().ok()?;
+        let size = match bits.next() {
+            Some(size) => size.parse().ok()?,
+            None => crate::DEFAULT_SIZE,
+        };
+
+        let mut board = BoardV2::with_size(size)?;
+        board.horizontal = horizontal;
+        board.vertical = vertical;
+        board.positions[0] = player1_pos;
+        board.positions[1] = player2_pos;
+        board.walls[0] = player1_walls;
+        board.walls[1] = player2_walls;
+        if let Some(player3_pos) = bits.next() {
+            board.players = 4;
+            board.positions[2] = Position::new(player3_pos.parse::<u8>().ok()?.checked_sub(1)?)?;
+            board.positions[3] = Position::new(bits.next()?.parse::<u8>().ok()?.checked_sub(1)?)?;
+            board.walls[2] = bits.next()?.parse().ok()?;
+            board.walls[3] = bits.next()?.parse().ok()?;
+        }
+        board.update_passability();
+        board.set_side_to_move(Player::Player1);
+        Some(board)
     }
 
     pub fn repr_string(&self) -> String {
-        format!(
+        let repr = format!(
             "{} {} {} {} {} {}",
             self.horizontal,
             self.vertical,
-            self.player1_pos,
-            self.player2_pos,
-            self.player1_walls,
-            self.player2_walls,
-        )
+            self.positions[0],
+            self.positions[1],
+            self.walls[0],
+            self.walls[1],
+        );
+        if self.players == 4 {
+            format!(
+                "{} {} {} {} {} {}",
+                repr, self.size, self.positions[2], self.positions[3], self.walls[2], self.walls[3],
+            )
+        } else if self.size == crate::DEFAULT_SIZE {
+            repr
+        } else {
+            format!("{} {}", repr, self.size)
+        }
     }
 
-    fn bit_mask(p: (u8, u8)) -> Option<u64> {
-        BoardV2::bit_idx(p).map(|b| 1 << b)
+    fn bit_mask(&self, p: (u8, u8)) -> Option<u64> {
+        self.bit_idx(p).map(|b| 1 << b)
     }
 
-    fn bit_idx((x, y): (u8, u8)) -> Option<u8> {
-        if x < 8 && y < 8 {
+    fn bit_idx(&self, (x, y): (u8, u8)) -> Option<u8> {
+        if x < self.size - 1 && y < self.size - 1 {
             Some(x * 8 + y)
         } else {
             None
         }
     }
 
-    fn set_player_location(&mut self, player: Player, pos: (u8, u8)) -> Result<(), ()> {
-        *match player {
-            super::Player::Player1 => &mut self.player1_pos,
-            super::Player::Player2 => &mut self.player2_pos,
-        } = pos.try_into()?;
+    fn set_player_location(&mut self, player: Player, pos: (u8, u8)) -> Result<(), IllegalMove> {
+        if pos.0 >= self.size || pos.1 >= self.size {
+            return Err(IllegalMove::OutOfBounds);
+        }
+        self.positions[player.index()] = pos.try_into().map_err(|_| IllegalMove::OutOfBounds)?;
         Ok(())
     }
 
     fn is_passible_down(&self, pos: (u8, u8)) -> bool {
         let neighbor = Direction::Left
-            .shift(pos)
-            .and_then(|np| BoardV2::bit_mask(np).map(|mask| mask & self.horizontal == 0))
+            .shift(pos, self.size)
+            .and_then(|np| self.bit_mask(np).map(|mask| mask & self.horizontal == 0))
             .unwrap_or(true);
 
-        let this = BoardV2::bit_mask(pos)
+        let this = self
+            .bit_mask(pos)
             .map(|mask| mask & self.horizontal == 0)
             .unwrap_or(true);
 
@@ -354,11 +848,12 @@ impl BoardV2 {
     }
     fn is_passible_right(&self, pos: (u8, u8)) -> bool {
         let neighbor = Direction::Up
-            .shift(pos)
-            .and_then(|np| BoardV2::bit_mask(np).map(|mask| mask & self.vertical == 0))
+            .shift(pos, self.size)
+            .and_then(|np| self.bit_mask(np).map(|mask| mask & self.vertical == 0))
             .unwrap_or(true);
 
-        let this = BoardV2::bit_mask(pos)
+        let this = self
+            .bit_mask(pos)
             .map(|mask| mask & self.vertical == 0)
             .unwrap_or(true);
 
@@ -366,64 +861,6 @@ impl BoardV2 {
     }
 }
 
-pub fn can_reach_goal_v2(board: &impl Board, player: Player) -> bool {
-    let y_range = match player {
-        Player::Player1 => [8u8, 7, 6, 5, 4, 3, 2, 1, 0],
-        Player::Player2 => [0, 1, 2, 3, 4, 5, 6, 7, 8u8],
-    };
-
-    let goal_y = match player {
-        Player::Player1 => 8u8,
-        Player::Player2 => 0,
-    };
-
-    let mut hit_map = [[false; 9]; 9];
-    let mut solved_map = [[false; 9]; 9];
-    let (player_x, player_y) = board.player_location(player);
-    hit_map[player_y as usize][player_x as usize] = true;
-
-    'outer: loop {
-        for y in &y_range {
-            let y = *y;
-            for x in 0..9u8 {
-                if hit_map[y as usize][x as usize] && !solved_map[y as usize][x as usize] {
-                    solved_map[y as usize][x as usize] = true;
-                    let directions = [
-                        Direction::Down,
-                        Direction::Up,
-                        Direction::Left,
-                        Direction::Right,
-                    ]
-                    .iter();
-
-                    let mut found_one = false;
-                    for direction in directions {
-                        let direction = *direction;
-                        if let Some((nx, ny)) = direction.shift((x, y)) {
-                            if !hit_map[ny as usize][nx as usize] {
-                                if direction
-                                    .shift((x, y))
-                                    .map_or(false, |nl| board.is_passible((x, y), nl))
-                                {
-                                    if ny == goal_y {
-                                        return true;
-                                    }
-                                    hit_map[ny as usize][nx as usize] = true;
-                                    found_one = true;
-                                }
-                            }
-                        }
-                    }
-                    if found_one {
-                        continue 'outer;
-                    }
-                }
-            }
-        }
-        return false;
-    }
-}
-
 impl From<Position> for (u8, u8) {
     fn from(p: Position) -> Self {
         let Position(p) = p;
@@ -435,6 +872,9 @@ impl TryFrom<(u8, u8)> for Position {
     type Error = ();
 
     fn try_from((x, y): (u8, u8)) -> Result<Self, ()> {
+        if x > 8 || y > 8 {
+            return Err(());
+        }
         let position = (y * 9 + x + 1).try_into().map_err(|_| ())?;
         NonZeroU8::new(position).map(Self).ok_or(())
     }
@@ -494,14 +934,24 @@ mod tests {
 
     #[test]
     fn can_reach_goal_works() {
-        let board = BoardV2 {
+        let mut board = BoardV2 {
             horizontal: 18015223143202816,
             vertical: 2147483648,
-            player1_pos: Position(23.try_into().unwrap()),
-            player2_pos: Position(68.try_into().unwrap()),
-            player1_walls: 8,
-            player2_walls: 8,
+            positions: [
+                Position(23.try_into().unwrap()),
+                Position(68.try_into().unwrap()),
+                Player::Player3.start(9).try_into().unwrap(),
+                Player::Player4.start(9).try_into().unwrap(),
+            ],
+            walls: [8, 8, 10, 10],
+            size: 9,
+            players: 2,
+            zobrist: 0,
+            to_move: Player::Player1,
+            open_right: 0,
+            open_down: 0,
         };
+        board.update_passability();
 
         board.is_legal(
             Player::Player1,
@@ -567,6 +1017,157 @@ mod tests {
         }
     }
 
+    #[test]
+    fn illegal_moves_report_a_reason() {
+        let mut board = BoardV2::empty();
+        board
+            .add_wall(Player::Player1, (3, 3), Orientation::Horizontal)
+            .unwrap();
+        assert_eq!(
+            Err(IllegalMove::WallOverlaps),
+            board.check_move(
+                Player::Player2,
+                &Move::AddWall {
+                    location: (4, 3),
+                    orientation: Orientation::Horizontal
+                }
+            )
+        );
+        assert_eq!(
+            Err(IllegalMove::OutOfBounds),
+            board.check_move(
+                Player::Player2,
+                &Move::AddWall {
+                    location: (8, 3),
+                    orientation: Orientation::Horizontal
+                }
+            )
+        );
+        assert_eq!(
+            Err(IllegalMove::IllegalPawnMove),
+            board.check_move(Player::Player1, &Move::MoveTo(4, 2))
+        );
+
+        let mut board = BoardV2::empty();
+        for x in &[0, 2, 4, 6] {
+            board
+                .add_wall(Player::Player1, (*x, 0), Orientation::Horizontal)
+                .unwrap();
+        }
+        assert_eq!(
+            Err(IllegalMove::WallBlocksPath),
+            board.check_move(
+                Player::Player2,
+                &Move::AddWall {
+                    location: (7, 0),
+                    orientation: Orientation::Vertical
+                }
+            )
+        );
+
+        board.walls[0] = 0;
+        assert_eq!(
+            Err(IllegalMove::NoWallsLeft),
+            board.check_move(
+                Player::Player1,
+                &Move::AddWall {
+                    location: (3, 5),
+                    orientation: Orientation::Vertical
+                }
+            )
+        );
+        assert_eq!(
+            Err(IllegalMove::NoWallsLeft),
+            board.add_wall(Player::Player1, (3, 5), Orientation::Vertical)
+        );
+    }
+
+    #[test]
+    fn smaller_boards() {
+        for size in &[3, 5, 7] {
+            let board = BoardV2::with_size(*size).unwrap();
+            let last = size - 1;
+            assert_eq!((size / 2, 0), board.player_location(Player::Player1));
+            assert_eq!((size / 2, last), board.player_location(Player::Player2));
+            assert_eq!(size + 1, board.available_walls(Player::Player1));
+            assert_eq!(Some(last), board.distance_to_goal(Player::Player1));
+            assert_eq!(Some(last), board.distance_to_goal(Player::Player2));
+            assert!(!board.is_passible((last, 1), (last + 1, 1)));
+            assert!(!board.is_legal(
+                Player::Player1,
+                &Move::AddWall {
+                    location: (last, 0),
+                    orientation: Orientation::Vertical
+                }
+            ));
+            assert!(board.is_legal(
+                Player::Player1,
+                &Move::AddWall {
+                    location: (last - 1, 0),
+                    orientation: Orientation::Vertical
+                }
+            ));
+            assert_eq!(BoardV2::from_repr_string(&board.repr_string()), Some(board));
+        }
+        assert!(BoardV2::with_size(8).is_none());
+        assert!(BoardV2::with_size(11).is_none());
+        assert!(crate::v1::BoardV1::with_size(11).is_some());
+    }
+
+    #[test]
+    fn eleven_by_eleven() {
+        let mut board = crate::v1::BoardV1::with_size(11).unwrap();
+        assert_eq!((5, 0), board.player_location(Player::Player1));
+        assert_eq!(Some(10), board.distance_to_goal(Player::Player2));
+        assert_eq!(12, board.available_walls(Player::Player2));
+        board
+            .add_wall(Player::Player1, (9, 9), Orientation::Horizontal)
+            .unwrap();
+        assert!(!board.is_passible((10, 9), (10, 10)));
+        board.move_token(Player::Player1, (5, 10)).unwrap();
+        assert_eq!(Some(Player::Player1), board.result());
+    }
+
+    #[test]
+    fn four_players() {
+        assert!(BoardV2::with_players(9, 3).is_none());
+        let mut board = BoardV2::with_players(9, 4).unwrap();
+        assert_eq!(
+            board.players(),
+            &[
+                Player::Player1,
+                Player::Player3,
+                Player::Player2,
+                Player::Player4
+            ]
+        );
+        assert_eq!(Player::Player1, Player::Player4.next(4));
+        assert_eq!((8, 4), board.player_location(Player::Player3));
+        assert_eq!((0, 4), board.player_location(Player::Player4));
+        assert_eq!(5, board.available_walls(Player::Player4));
+        assert_eq!(Some(8), board.distance_to_goal(Player::Player3));
+        assert_eq!(
+            BoardV2::from_repr_string(&board.repr_string()),
+            Some(board.clone())
+        );
+
+        // pawns can't share a square, but can be jumped
+        board.move_token(Player::Player3, (5, 4)).unwrap();
+        board.move_token(Player::Player1, (4, 5)).unwrap();
+        assert!(!board.is_legal(Player::Player4, &Move::MoveTo(5, 4)));
+        board.move_token(Player::Player4, (4, 4)).unwrap();
+        assert!(board.is_legal(Player::Player4, &Move::MoveTo(6, 4)));
+        assert!(board.is_legal(Player::Player4, &Move::MoveTo(4, 6)));
+
+        board.move_token(Player::Player3, (0, 0)).unwrap();
+        assert_eq!(Some(Player::Player3), board.result());
+
+        let v1: crate::v1::BoardV1 = board.clone().into();
+        assert_eq!(4, v1.num_players());
+        assert_eq!((0, 0), v1.player_location(Player::Player3));
+        assert_eq!(Some(Player::Player3), v1.result());
+    }
+
     #[test]
     fn test_position_conversions() {
         fn check(a: u8, b: u8) {
@@ -692,36 +1293,252 @@ mod tests {
         assert_eq!(Some(9), packed.distance_to_goal(Player::Player2));
         assert_eq!(Some(9), packed.distance_to_goal(Player::Player1));
     }
+
+    fn sorted_moves(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_key(|mov| format!("{:?}", mov));
+        moves
+    }
+
+    fn assert_boards_agree(v1: &crate::v1::BoardV1, v2: &BoardV2) {
+        let size = v2.size();
+        assert!(*v1 == crate::v1::BoardV1::from(v2.clone()));
+        assert_eq!(v1.result(), v2.result());
+        for player in v2.players() {
+            assert_eq!(v1.player_location(*player), v2.player_location(*player));
+            assert_eq!(v1.available_walls(*player), v2.available_walls(*player));
+            assert_eq!(
+                v1.distance_to_goal(*player),
+                v2.distance_to_goal(*player),
+                "{:?}",
+                player
+            );
+            assert_eq!(
+                sorted_moves(v1.legal_moves(*player)),
+                sorted_moves(v2.legal_moves(*player)),
+                "{:?}",
+                player
+            );
+        }
+        for x in 0..size {
+            for y in 0..size {
+                assert_eq!(
+                    v1.get_wall_state((x, y)),
+                    v2.get_wall_state((x, y)),
+                    "({}, {})",
+                    x,
+                    y
+                );
+                for direction in Direction::ALL.iter() {
+                    if let Some(next) = direction.shift((x, y), size) {
+                        assert_eq!(
+                            v1.is_passible((x, y), next),
+                            v2.is_passible((x, y), next),
+                            "({}, {}) {:?}",
+                            x,
+                            y,
+                            direction
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flood_fill_sees_sealed_rows() {
+        let mut board = BoardV2::with_size(5).unwrap();
+        for x in &[0, 2] {
+            board
+                .add_wall(Player::Player1, (*x, 0), Orientation::Horizontal)
+                .unwrap();
+        }
+        assert_eq!(Some(6), board.distance_to_goal(Player::Player1));
+        // shuts player 1 into the first row
+        board
+            .add_wall(Player::Player2, (3, 0), Orientation::Vertical)
+            .unwrap();
+        assert!(!board.can_reach_goal(Player::Player1));
+        assert!(board.can_reach_goal(Player::Player2));
+        assert!(!board.everyone_can_reach_goal());
+
+        board.remove_wall(Player::Player2, (3, 0), Orientation::Vertical);
+        assert!(board.everyone_can_reach_goal());
+        assert_eq!(Some(6), board.distance_to_goal(Player::Player1));
+    }
+
+    #[test]
+    fn add_wall_rejects_overlaps() {
+        fn check<B: Board>() {
+            let mut board = B::empty();
+            board
+                .add_wall(Player::Player1, (3, 3), Orientation::Horizontal)
+                .unwrap();
+            for (location, orientation) in &[
+                ((4, 3), Orientation::Horizontal),
+                ((2, 3), Orientation::Horizontal),
+                ((3, 3), Orientation::Horizontal),
+                ((3, 3), Orientation::Vertical),
+            ] {
+                assert_eq!(
+                    Err(IllegalMove::WallOverlaps),
+                    board.add_wall(Player::Player2, *location, *orientation)
+                );
+            }
+            assert_eq!(10, board.available_walls(Player::Player2));
+            board
+                .add_wall(Player::Player2, (5, 3), Orientation::Horizontal)
+                .unwrap();
+            board
+                .add_wall(Player::Player2, (4, 2), Orientation::Vertical)
+                .unwrap();
+        }
+        check::<BoardV2>();
+        check::<crate::v1::BoardV1>();
+    }
+
+    #[test]
+    fn finds_blocking_walls() {
+        let mut board = BoardV2::with_size(5).unwrap();
+        assert_eq!(0, board.blocking_walls(Orientation::Horizontal));
+        assert_eq!(0, board.blocking_walls(Orientation::Vertical));
+
+        for x in &[0, 2] {
+            board
+                .add_wall(Player::Player1, (*x, 0), Orientation::Horizontal)
+                .unwrap();
+        }
+        // d1v would shut player 1 into row 1
+        assert_eq!(1 << (3 * 8), board.blocking_walls(Orientation::Vertical));
+        assert_eq!(0, board.blocking_walls(Orientation::Horizontal));
+        assert!(!board
+            .legal_moves(Player::Player2)
+            .contains(&"d1v".parse().unwrap()));
+    }
+
+    #[test]
+    fn zobrist_keys() {
+        let wall = |notation: &str| notation.parse::<Move>().unwrap();
+        let mut a = BoardV2::empty();
+        a.apply_move(&wall("a1h"), Player::Player1).unwrap();
+        a.apply_move(&wall("c1h"), Player::Player2).unwrap();
+        let mut b = BoardV2::empty();
+        b.apply_move(&wall("c1h"), Player::Player1).unwrap();
+        b.apply_move(&wall("a1h"), Player::Player2).unwrap();
+        assert_eq!(a.zobrist(), b.zobrist());
+
+        let start = BoardV2::empty();
+        let mut other_side = start.clone();
+        other_side.set_side_to_move(Player::Player2);
+        assert_ne!(start.zobrist(), other_side.zobrist());
+
+        let mut fewer_walls = start.clone();
+        fewer_walls.set_available_walls(Player::Player1, 9);
+        assert_ne!(start.zobrist(), fewer_walls.zobrist());
+
+        let applied = a.make_move(&Move::MoveTo(4, 1), Player::Player1).unwrap();
+        assert_ne!(a.zobrist(), b.zobrist());
+        a.undo_move(&applied);
+        assert_eq!(a.zobrist(), b.zobrist());
+    }
+
+    #[test]
+    fn out_of_turn_moves_keep_one_side_to_move() {
+        let board = BoardV2::with_players(9, 4).unwrap();
+        let moves = [Move::MoveTo(4, 7), "e5h".parse().unwrap()];
+        for mov in moves.iter() {
+            let mut moved = board.clone();
+            let applied = moved.make_move(mov, Player::Player2).unwrap();
+            assert_eq!(Player::Player4, moved.side_to_move());
+            let mut expected = moved.clone();
+            expected.set_side_to_move(Player::Player4);
+            assert_eq!(expected.zobrist(), moved.zobrist());
+            assert_eq!(moved.flip().flip(), moved);
+            serde_json::to_string(&moved).unwrap();
+
+            moved.undo_move(&applied);
+            assert_eq!(Player::Player2, moved.side_to_move());
+            let mut expected = board.clone();
+            expected.set_side_to_move(Player::Player2);
+            assert_eq!(expected, moved);
+        }
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::test_runner::Config::with_cases(32))]
+
+        #[test]
+        fn boards_agree_over_random_games(
+            size in proptest::sample::select(vec![5u8, 7, 9]),
+            players in proptest::sample::select(vec![2u8, 4]),
+            choices in proptest::collection::vec(proptest::prelude::any::<proptest::sample::Index>(), 0..40),
+        ) {
+            let mut v2 = BoardV2::with_players(size, players).unwrap();
+            let mut v1 = crate::v1::BoardV1::with_players(size, players).unwrap();
+            let mut player = Player::Player1;
+            let mut history = vec![];
+            assert_boards_agree(&v1, &v2);
+
+            for choice in choices {
+                if v2.result().is_some() {
+                    break;
+                }
+                let moves = v2.legal_moves(player);
+                let mov = choice.get(&moves);
+                v1.apply_move(mov, player).unwrap();
+                history.push(v2.make_move(mov, player).unwrap());
+                assert_boards_agree(&v1, &v2);
+                player = player.next(players);
+                assert_eq!(v2.compute_zobrist(player), v2.zobrist());
+                assert_eq!(v2.zobrist(), v1.position_key(player));
+                for orientation in [Orientation::Horizontal, Orientation::Vertical].iter() {
+                    let brute_force = (0..64)
+                        .filter(|bit| v2.open_wall_slots(*orientation) & 1 << bit != 0)
+                        .filter(|bit| v2.wall_blocks_path((bit / 8, bit % 8), *orientation))
+                        .fold(0, |mask, bit| mask | 1 << bit);
+                    assert_eq!(brute_force, v2.blocking_walls(*orientation));
+                }
+            }
+
+            for applied in history.iter().rev() {
+                v1.undo_move(applied);
+                v2.undo_move(applied);
+                assert!(v1 == crate::v1::BoardV1::from(v2.clone()));
+            }
+            assert!(v2 == BoardV2::with_players(size, players).unwrap());
+        }
+    }
 }
 
 impl From<BoardV2> for crate::v1::BoardV1 {
     fn from(board: BoardV2) -> Self {
-        let mut res = crate::v1::BoardV1::empty();
-        res.player1_loc = board.player1_pos.into();
-        res.player2_loc = board.player2_pos.into();
-        res.player1_walls = board.player1_walls;
-        res.player2_walls = board.player2_walls;
-
-        for y in 0..9u8 {
-            for x in 0..9u8 {
+        let mut res = crate::v1::BoardV1::with_players(board.size, board.players).unwrap();
+        for player in board.players() {
+            *res.location_mut(player) = board.player_location(*player);
+        }
+        res.walls = board.walls;
+
+        let last = board.size - 1;
+        for y in 0..board.size {
+            for x in 0..board.size {
                 let loc = (x, y);
                 let cell = res.cell_mut(&loc);
-                if x != 8 {
+                if x != last {
                     cell.right = if board.is_passible_right(loc) {
                         crate::v1::WallState::Open
                     } else {
                         crate::v1::WallState::Wall
                     };
                 }
-                if y != 8 {
+                if y != last {
                     cell.bottom = if board.is_passible_down(loc) {
                         crate::v1::WallState::Open
                     } else {
                         crate::v1::WallState::Wall
                     };
                 }
-                if x != 8 && y != 8 {
-                    cell.joint = if BoardV2::bit_mask((x, y))
+                if x != last && y != last {
+                    cell.joint = if board
+                        .bit_mask((x, y))
                         .map(|m| (m & (board.horizontal | board.vertical)) == 0)
                         .unwrap_or(false)
                     {
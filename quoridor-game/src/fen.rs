@@ -0,0 +1,316 @@
+//! FEN-style position strings. A position is written as five fields, plus the
+//! board size when it isn't 9:
+//!
+//! ```text
+//! e3,d7 a1 e1,e9 10,10 1
+//! ```
+//!
+//! horizontal walls, vertical walls (`-` for none), pawn squares and walls
+//! left for each player by player number (player 1, 2, then 3 and 4 in a
+//! four player game, even though player 3 moves second) and the number of
+//! the player to move. Squares and walls use
+//! the notation from [`crate::notation`].
+
+use crate::{
+    is_valid_player_count, walls_per_player, Board, IllegalMove, Move, Orientation, Player,
+    DEFAULT_SIZE,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use parse_display::Display;
+
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+pub enum FenError {
+    #[display("expected 5 or 6 fields")]
+    WrongFieldCount,
+    #[display("unsupported board size")]
+    BadSize,
+    #[display("can't read square {0:?}")]
+    BadSquare(String),
+    #[display("expected a pawn and wall count for each of 2 or 4 players")]
+    BadPlayerCount,
+    #[display("pawns share the square {0}")]
+    PawnsOverlap(Move),
+    #[display("pawn on {0} is off the board")]
+    PawnOutOfBounds(Move),
+    #[display("wall {0}: {1}")]
+    IllegalWall(Move, IllegalMove),
+    #[display("more walls than the players started with")]
+    BadWallCounts,
+    #[display("{0:?} is not a player in this game")]
+    BadSide(String),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FenError {}
+
+fn squares(field: &str) -> Result<Vec<(u8, u8)>, FenError> {
+    if field == "-" {
+        return Ok(vec![]);
+    }
+    field
+        .split(',')
+        .map(|square| match square.parse() {
+            Ok(Move::MoveTo(x, y)) => Ok((x, y)),
+            _ => Err(FenError::BadSquare(square.to_string())),
+        })
+        .collect()
+}
+
+fn square_list(squares: impl Iterator<Item = (u8, u8)>) -> String {
+    let list = squares
+        .map(|(x, y)| Move::MoveTo(x, y).to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    if list.is_empty() {
+        "-".to_string()
+    } else {
+        list
+    }
+}
+
+/// Writes `board` with `side_to_move` to play as a position string.
+pub fn to_fen<B: Board>(board: &B, side_to_move: Player) -> String {
+    let size = board.size();
+    let walls = |orientation| {
+        square_list(
+            (0..size - 1)
+                .flat_map(|y| (0..size - 1).map(move |x| (x, y)))
+                .filter(|location| board.get_wall_state(*location) == Some(orientation)),
+        )
+    };
+    let players = &Player::ALL[..board.num_players() as usize];
+    let mut fen = format!(
+        "{} {} {} {} {}",
+        walls(Orientation::Horizontal),
+        walls(Orientation::Vertical),
+        square_list(players.iter().map(|p| board.player_location(*p))),
+        players
+            .iter()
+            .map(|p| board.available_walls(*p).to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        side_to_move.index() + 1,
+    );
+    if size != DEFAULT_SIZE {
+        fen.push_str(&format!(" {}", size));
+    }
+    fen
+}
+
+/// Reads a position string, checking that it describes a reachable-looking
+/// position: walls on the board that don't overlap or cut anyone off, pawns
+/// on distinct squares and no more walls than the players started with.
+pub fn from_fen<B: Board>(fen: &str) -> Result<(B, Player), FenError> {
+    let fields = fen.split_ascii_whitespace().collect::<Vec<_>>();
+    let size = match fields.len() {
+        5 => DEFAULT_SIZE,
+        6 => fields[5].parse().map_err(|_| FenError::BadSize)?,
+        _ => return Err(FenError::WrongFieldCount),
+    };
+
+    let horizontal = squares(fields[0])?;
+    let vertical = squares(fields[1])?;
+    let pawns = squares(fields[2])?;
+    let wall_counts = fields[3]
+        .split(',')
+        .map(|count| count.parse::<u8>().map_err(|_| FenError::BadWallCounts))
+        .collect::<Result<Vec<_>, _>>()?;
+    if pawns.len() != wall_counts.len() {
+        return Err(FenError::BadPlayerCount);
+    }
+    let players = pawns.len() as u8;
+    if !is_valid_player_count(players) {
+        return Err(FenError::BadPlayerCount);
+    }
+    let side = fields[4]
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| {
+            Player::ALL[..players as usize]
+                .get(n.checked_sub(1)?)
+                .copied()
+        })
+        .ok_or_else(|| FenError::BadSide(fields[4].to_string()))?;
+
+    let board = build(size, &horizontal, &vertical, &pawns, &wall_counts, side)?;
+    Ok((board, side))
+}
+
+/// Sets up a board from its parts with the checks `from_fen` makes. Pawns and
+/// walls left are given by player number, one for each player.
+pub(crate) fn build<B: Board>(
+    size: u8,
+    horizontal: &[(u8, u8)],
+    vertical: &[(u8, u8)],
+    pawns: &[(u8, u8)],
+    wall_counts: &[u8],
+    side: Player,
+) -> Result<B, FenError> {
+    let players = pawns.len() as u8;
+    if pawns.len() != wall_counts.len() || !is_valid_player_count(players) {
+        return Err(FenError::BadPlayerCount);
+    }
+    if side.index() >= players as usize {
+        return Err(FenError::BadSide((side.index() + 1).to_string()));
+    }
+    let mut board = B::with_players(size, players).ok_or(FenError::BadSize)?;
+    let by_number = &Player::ALL[..players as usize];
+
+    for (i, (player, (x, y))) in by_number.iter().zip(pawns).enumerate() {
+        if pawns[..i].contains(&(*x, *y)) {
+            return Err(FenError::PawnsOverlap(Move::MoveTo(*x, *y)));
+        }
+        board
+            .move_token(*player, (*x, *y))
+            .map_err(|_| FenError::PawnOutOfBounds(Move::MoveTo(*x, *y)))?;
+    }
+
+    let placed = horizontal.len() + vertical.len();
+    let total = walls_per_player(size, players) as usize * players as usize;
+    let remaining = wall_counts.iter().map(|n| *n as usize).sum::<usize>();
+    if placed + remaining > total {
+        return Err(FenError::BadWallCounts);
+    }
+    board.set_available_walls(Player::Player1, placed as u8);
+    let walls = horizontal
+        .iter()
+        .map(|location| (*location, Orientation::Horizontal))
+        .chain(
+            vertical
+                .iter()
+                .map(|location| (*location, Orientation::Vertical)),
+        );
+    for (location, orientation) in walls {
+        let wall = Move::AddWall {
+            location,
+            orientation,
+        };
+        board
+            .check_move(Player::Player1, &wall)
+            .and_then(|()| board.apply_move(&wall, Player::Player1))
+            .map_err(|e| FenError::IllegalWall(wall.clone(), e))?;
+    }
+    for (player, walls) in by_number.iter().zip(wall_counts) {
+        board.set_available_walls(*player, *walls);
+    }
+    board.set_side_to_move(side);
+
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitpacked::BoardV2, v1::BoardV1};
+
+    fn round_trip(fen: &str) {
+        let (v2, side) = from_fen::<BoardV2>(fen).unwrap();
+        assert_eq!(fen, to_fen(&v2, side));
+        let (v1, side) = from_fen::<BoardV1>(fen).unwrap();
+        assert_eq!(fen, to_fen(&v1, side));
+        assert!(v1 == BoardV1::from(v2));
+    }
+
+    #[test]
+    fn reads_and_writes_positions() {
+        assert_eq!(
+            "- - e1,e9 10,10 1",
+            to_fen(&BoardV2::empty(), Player::Player1)
+        );
+        assert_eq!(
+            "- - c1,c5,e3,a3 3,3,3,3 3 5",
+            to_fen(&BoardV2::with_players(5, 4).unwrap(), Player::Player3)
+        );
+        round_trip("e3,d7 a1 e1,e9 9,8 1");
+        round_trip("a1,c1,e1,g1 h2 e4,d4 5,0 2");
+        round_trip("b2 - a3,c1,c5,e3 1,2,0,1 4 5");
+        round_trip("a4,c4,e4,g4 - e1,e9 6,6 1");
+
+        let (board, side) = from_fen::<BoardV2>("e3,d7 a1 e2,e8 9,8 2").unwrap();
+        assert_eq!(Player::Player2, side);
+        assert_eq!((4, 1), board.player_location(Player::Player1));
+        assert_eq!(Some(Orientation::Horizontal), board.get_wall_state((3, 6)));
+        assert_eq!(Some(Orientation::Vertical), board.get_wall_state((0, 0)));
+        assert_eq!(8, board.available_walls(Player::Player2));
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        let error = |fen| from_fen::<BoardV2>(fen).map(|_| ()).unwrap_err();
+        assert_eq!(FenError::WrongFieldCount, error("- - e1,e9 10,10"));
+        assert_eq!(FenError::BadSize, error("- - e1,e9 10,10 1 8"));
+        assert_eq!(FenError::BadSize, error("- - e1,e9 10,10 1 11"));
+        assert_eq!(
+            FenError::BadSquare("e0".to_string()),
+            error("- - e1,e0 10,10 1")
+        );
+        assert_eq!(
+            FenError::BadSquare("e3h".to_string()),
+            error("e3h - e1,e9 10,10 1")
+        );
+        assert_eq!(FenError::BadPlayerCount, error("- - e1,e9,a5 10,10,10 1"));
+        assert_eq!(FenError::BadPlayerCount, error("- - e1,e9 10 1"));
+        assert_eq!(
+            FenError::PawnsOverlap(Move::MoveTo(4, 4)),
+            error("- - e5,e5 10,10 1")
+        );
+        assert_eq!(
+            FenError::PawnOutOfBounds(Move::MoveTo(9, 0)),
+            error("- - j1,e9 10,10 1")
+        );
+        assert_eq!(
+            FenError::BadSide("3".to_string()),
+            error("- - e1,e9 10,10 3")
+        );
+        assert_eq!(
+            FenError::BadSide("x".to_string()),
+            error("- - e1,e9 10,10 x")
+        );
+        assert_eq!(FenError::BadWallCounts, error("e3 - e1,e9 10,10 1"));
+        assert_eq!(
+            FenError::IllegalWall(
+                Move::AddWall {
+                    location: (4, 2),
+                    orientation: Orientation::Vertical
+                },
+                IllegalMove::WallOverlaps
+            ),
+            error("e3 e3 e1,e9 9,9 1")
+        );
+        assert_eq!(
+            FenError::IllegalWall(
+                Move::AddWall {
+                    location: (5, 2),
+                    orientation: Orientation::Horizontal
+                },
+                IllegalMove::WallOverlaps
+            ),
+            error("e3,f3 - e1,e9 9,9 1")
+        );
+        assert_eq!(
+            FenError::IllegalWall(
+                Move::AddWall {
+                    location: (8, 0),
+                    orientation: Orientation::Horizontal
+                },
+                IllegalMove::OutOfBounds
+            ),
+            error("i1 - e1,e9 9,10 1")
+        );
+        assert_eq!(
+            FenError::IllegalWall(
+                Move::AddWall {
+                    location: (7, 0),
+                    orientation: Orientation::Vertical
+                },
+                IllegalMove::WallBlocksPath
+            ),
+            error("a1,c1,e1,g1,h2 h1 e4,d4 5,5 1")
+        );
+    }
+}
@@ -1,9 +1,58 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod any;
 pub mod bitpacked;
+pub mod fen;
+pub mod movelist;
+pub mod notation;
+pub mod perft;
+pub mod pgn;
+pub mod record;
+mod serialize;
+pub mod symmetry;
 pub mod v1;
 
+pub use movelist::MoveList;
+pub use record::GameRecord;
+
+use alloc::vec::Vec;
+use core::hash::Hash;
 use parse_display::{Display, FromStr};
 use serde::{Deserialize, Serialize};
-use std::hash::Hash;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, Display)]
+pub enum IllegalMove {
+    #[display("the wall overlaps an existing wall")]
+    WallOverlaps,
+    #[display("the wall would block a player from reaching their goal")]
+    WallBlocksPath,
+    #[display("there are no walls left to place")]
+    NoWallsLeft,
+    #[display("the location is off the board")]
+    OutOfBounds,
+    #[display("the pawn can't move or jump to that square")]
+    IllegalPawnMove,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IllegalMove {}
+
+pub const DEFAULT_SIZE: u8 = 9;
+pub const MAX_SIZE: u8 = 11;
+
+/// Boards have an odd number of rows and columns so that the pawns start in
+/// the middle column.
+pub fn is_valid_size(size: u8) -> bool {
+    (3..=MAX_SIZE).contains(&size) && size % 2 == 1
+}
+
+/// Each player gets ten walls on the standard 9x9 board (five in a four player
+/// game), scaled to the size.
+pub fn walls_per_player(size: u8, players: u8) -> u8 {
+    (size + 1) * 2 / players
+}
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Orientation {
@@ -20,35 +69,77 @@ impl Orientation {
     }
 }
 
-pub trait Board {
-    fn empty() -> Self;
+pub trait Board: Sized {
+    fn empty() -> Self {
+        Self::with_size(DEFAULT_SIZE).unwrap()
+    }
+
+    fn with_size(size: u8) -> Option<Self> {
+        Self::with_players(size, 2)
+    }
+
+    fn with_players(size: u8, players: u8) -> Option<Self>;
+
+    fn size(&self) -> u8;
+
+    fn num_players(&self) -> u8;
+
+    fn players(&self) -> &'static [Player] {
+        Player::seats(self.num_players())
+    }
 
     fn available_walls(&self, player: Player) -> u8;
 
+    fn set_available_walls(&mut self, player: Player, walls: u8);
+
+    /// Tells boards that track whose turn it is (for hashing) who moves next
+    /// after a position has been set up.
+    fn set_side_to_move(&mut self, _player: Player) {}
+
+    /// A Zobrist key for the position with `to_move` to move, for
+    /// transposition tables. Every board type gives the same key for the
+    /// same position; `BoardV2` keeps its key up to date as moves are made,
+    /// others work it out each time.
+    fn position_key(&self, to_move: Player) -> u64 {
+        let mut key = bitpacked::side_key(to_move);
+        for y in 0..self.size() - 1 {
+            for x in 0..self.size() - 1 {
+                if let Some(orientation) = self.get_wall_state((x, y)) {
+                    key ^= bitpacked::wall_key((x, y), orientation);
+                }
+            }
+        }
+        for player in self.players() {
+            key ^= bitpacked::pawn_key(*player, self.player_location(*player));
+            key ^= bitpacked::walls_key(*player, self.available_walls(*player));
+        }
+        key
+    }
+
     fn add_wall(
         &mut self,
         player: Player,
         location: (u8, u8),
         orientation: Orientation,
-    ) -> Result<(), ()>;
+    ) -> Result<(), IllegalMove>;
 
     fn result(&self) -> Option<Player> {
-        if self.player_location(Player::Player1).1 == 8 {
-            Some(Player::Player1)
-        } else if self.player_location(Player::Player2).1 == 0 {
-            Some(Player::Player2)
-        } else {
-            None
-        }
+        self.players()
+            .iter()
+            .copied()
+            .find(|p| p.goal_distance(self.size(), self.player_location(*p)) == 0)
     }
 
-    fn move_token(&mut self, player: Player, new_location: (u8, u8)) -> Result<(), ()>;
+    fn move_token(&mut self, player: Player, new_location: (u8, u8)) -> Result<(), IllegalMove>;
     fn is_probably_legal(&self, player: Player, candidate_move: &Move) -> bool {
         self.is_legal(player, candidate_move)
     }
-    fn is_legal(&self, player: Player, candidate_move: &Move) -> bool;
+    fn is_legal(&self, player: Player, candidate_move: &Move) -> bool {
+        self.check_move(player, candidate_move).is_ok()
+    }
+    fn check_move(&self, player: Player, candidate_move: &Move) -> Result<(), IllegalMove>;
     fn get_wall_state(&self, location: (u8, u8)) -> Option<Orientation>;
-    fn apply_move(&mut self, candidate: &Move, player: Player) -> Result<(), ()> {
+    fn apply_move(&mut self, candidate: &Move, player: Player) -> Result<(), IllegalMove> {
         match candidate {
             Move::AddWall {
                 location,
@@ -58,29 +149,48 @@ pub trait Board {
         }
     }
 
+    /// Takes a wall back off the board and returns it to `player`.
+    fn remove_wall(&mut self, player: Player, location: (u8, u8), orientation: Orientation);
+
+    /// Like `apply_move`, but returns a token that `undo_move` can use to take
+    /// the move back.
+    fn make_move(&mut self, candidate: &Move, player: Player) -> Result<AppliedMove, IllegalMove> {
+        let applied = AppliedMove {
+            player,
+            mov: candidate.clone(),
+            from: self.player_location(player),
+        };
+        self.apply_move(candidate, player)?;
+        Ok(applied)
+    }
+
+    fn undo_move(&mut self, applied: &AppliedMove) {
+        match applied.mov {
+            Move::AddWall {
+                location,
+                orientation,
+            } => self.remove_wall(applied.player, location, orientation),
+            Move::MoveTo(..) => self
+                .move_token(applied.player, applied.from)
+                .expect("pawn returns to a square it came from"),
+        }
+    }
+
     fn player_location(&self, player: Player) -> (u8, u8);
 
     fn distance_to_goal(&self, player: Player) -> Option<u8> {
-        use std::cmp::Reverse;
-        use std::collections::BinaryHeap;
+        use alloc::collections::BinaryHeap;
+        use core::cmp::Reverse;
 
-        fn p1_d2g((_, y): (u8, u8)) -> u8 {
-            8 - y
-        }
-        fn p2_d2g((_, y): (u8, u8)) -> u8 {
-            y
-        }
-        let d2g = match player {
-            Player::Player1 => p1_d2g,
-            Player::Player2 => p2_d2g,
-        };
+        let size = self.size();
+        let d2g = |loc| player.goal_distance(size, loc);
 
         if d2g(self.player_location(player)) == 0 {
             return Some(0);
         }
 
-        let mut costs = [[0xffu8; 9]; 9];
-        let mut heap = BinaryHeap::with_capacity(81);
+        let mut costs = [[0xffu8; MAX_SIZE as usize]; MAX_SIZE as usize];
+        let mut heap = BinaryHeap::with_capacity(size as usize * size as usize);
 
         let starting_loc = self.player_location(player);
         costs[starting_loc.0 as usize][starting_loc.1 as usize] = 0;
@@ -94,7 +204,7 @@ pub trait Board {
                 Direction::Right,
             ]
             .iter()
-            .filter_map(|d| d.shift(loc))
+            .filter_map(|d| d.shift(loc, size))
             .filter(|(nx, ny)| self.is_passible(loc, (*nx, *ny)));
 
             let cost = costs[loc.0 as usize][loc.1 as usize] + 1;
@@ -102,7 +212,8 @@ pub trait Board {
                 if d2g(neighbor) == 0 {
                     return Some(cost);
                 }
-                if costs[neighbor.0 as usize][neighbor.1 as usize] != 0xff {
+                // a square can be found again by a shorter route later on
+                if costs[neighbor.0 as usize][neighbor.1 as usize] <= cost {
                     continue;
                 }
                 costs[neighbor.0 as usize][neighbor.1 as usize] = cost;
@@ -113,31 +224,229 @@ pub trait Board {
         None
     }
 
+    /// How far every square is from `player`'s goal, ignoring pawns.
+    fn distance_field(&self, player: Player) -> DistanceField {
+        let size = self.size();
+        let mut field = DistanceField::unreachable(size);
+        let mut queue = alloc::collections::VecDeque::new();
+        for x in 0..size {
+            for y in 0..size {
+                if player.goal_distance(size, (x, y)) == 0 {
+                    field.set((x, y), 0);
+                    queue.push_back(((x, y), 0));
+                }
+            }
+        }
+        while let Some((loc, distance)) = queue.pop_front() {
+            for direction in Direction::ALL.iter() {
+                if let Some(next) = direction.shift(loc, size) {
+                    if field.get(next).is_none() && self.is_passible(loc, next) {
+                        field.set(next, distance + 1);
+                        queue.push_back((next, distance + 1));
+                    }
+                }
+            }
+        }
+        field
+    }
+
+    /// One shortest route for `player`'s pawn to its goal, ignoring other
+    /// pawns: every square it steps on after the one it stands on.
+    fn shortest_path(&self, player: Player) -> Option<Vec<(u8, u8)>> {
+        let size = self.size();
+        let field = self.distance_field(player);
+        let mut at = self.player_location(player);
+        let mut path = Vec::with_capacity(field.get(at)? as usize);
+        while let Some(distance) = field.get(at).filter(|distance| *distance > 0) {
+            at = Direction::ALL
+                .iter()
+                .filter_map(|direction| direction.shift(at, size))
+                .find(|next| field.get(*next) == Some(distance - 1) && self.is_passible(at, *next))
+                .expect("every square but the goal has a neighbour closer to it");
+            path.push(at);
+        }
+        Some(path)
+    }
+
+    /// Like `distance_to_goal`, but moving the way `player`'s pawn really
+    /// can while the other pawns stand still: jumping over them where that
+    /// saves a step and going around them where they are in the way.
+    fn pawn_distance_to_goal(&self, player: Player) -> Option<u8> {
+        let field = self.distance_field(player);
+        let start = self.player_location(player);
+        let distance = field.get(start)?;
+        // Only pawns closer to the goal than this one can block it or be
+        // jumped to save a step.
+        if self
+            .players()
+            .iter()
+            .filter(|p| **p != player)
+            .all(|p| field.get(self.player_location(*p)).unwrap_or(u8::MAX) >= distance)
+        {
+            return Some(distance);
+        }
+
+        let mut steps = DistanceField::unreachable(self.size());
+        steps.set(start, 0);
+        let mut queue = alloc::collections::VecDeque::new();
+        queue.push_back((start, 0));
+        while let Some((loc, taken)) = queue.pop_front() {
+            if field.get(loc) == Some(0) {
+                return Some(taken);
+            }
+            let occupied = |square| square != start && self.is_occupied(square);
+            pawn_steps(self, loc, occupied, |next| {
+                if steps.get(next).is_none() {
+                    steps.set(next, taken + 1);
+                    queue.push_back((next, taken + 1));
+                }
+            });
+        }
+        None
+    }
+
+    /// How far ahead `player` is in a pure race where nobody places another
+    /// wall and `to_move` moves first, counted in single moves of any
+    /// player. Positive when `player` gets to its goal before everyone else,
+    /// so with two players and equal distances the side to move is ahead by
+    /// one. Uses `pawn_distance_to_goal`, or `distance_to_goal` for a pawn
+    /// that only the other pawns shut in.
+    fn race_margin(&self, player: Player, to_move: Player) -> Option<i16> {
+        let players = self.num_players();
+        let finish = |p: Player| {
+            let distance = self
+                .pawn_distance_to_goal(p)
+                .or_else(|| self.distance_to_goal(p))? as i16;
+            let mut seat = 0;
+            let mut turn = to_move;
+            while turn != p {
+                turn = turn.next(players);
+                seat += 1;
+            }
+            Some(if distance == 0 {
+                0
+            } else {
+                (distance - 1) * players as i16 + seat + 1
+            })
+        };
+
+        let own = finish(player)?;
+        let rival = self
+            .players()
+            .iter()
+            .filter(|p| **p != player)
+            .try_fold(i16::MAX, |rival, p| finish(*p).map(|f| rival.min(f)))?;
+        Some(rival - own)
+    }
+
+    fn everyone_can_reach_goal(&self) -> bool {
+        self.players()
+            .iter()
+            .all(|p| self.distance_to_goal(*p).is_some())
+    }
+
+    fn is_occupied(&self, location: (u8, u8)) -> bool {
+        self.players()
+            .iter()
+            .any(|p| self.player_location(*p) == location)
+    }
+
     fn is_passible(&self, location: (u8, u8), new_location: (u8, u8)) -> bool;
 
-    fn legal_moves(&self, player: Player) -> Vec<Move> {
-        all_moves()
-            .filter(|mov| self.is_legal(player, mov))
+    /// Every square `player`'s pawn may move to. A pawn steps to an open
+    /// neighbouring square, or jumps a neighbouring pawn. When the square behind
+    /// that pawn is cut off by a wall, the board edge or another pawn, it may
+    /// instead jump diagonally to either side of it.
+    fn pawn_moves(&self, player: Player) -> Vec<(u8, u8)> {
+        let mut moves = MoveList::new();
+        self.generate_pawn_moves(player, &mut moves);
+        moves
+            .iter()
+            .filter_map(|mov| match mov {
+                Move::MoveTo(x, y) => Some((*x, *y)),
+                Move::AddWall { .. } => None,
+            })
             .collect()
     }
+
+    /// Appends the pawn moves from `pawn_moves` to `moves`.
+    fn generate_pawn_moves(&self, player: Player, moves: &mut MoveList) {
+        let start = moves.len();
+        let occupied = |square| self.is_occupied(square);
+        pawn_steps(self, self.player_location(player), occupied, |(x, y)| {
+            let mov = Move::MoveTo(x, y);
+            if !moves[start..].contains(&mov) {
+                moves.push(mov);
+            }
+        });
+    }
+
+    fn legal_moves(&self, player: Player) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        self.generate_moves(player, &mut moves);
+        moves.to_vec()
+    }
+
+    /// Appends every legal move for `player` to `moves`, pawn moves first.
+    fn generate_moves(&self, player: Player, moves: &mut MoveList) {
+        self.generate_pawn_moves(player, moves);
+        for wall in all_walls(self.size()) {
+            if self.is_legal(player, &wall) {
+                moves.push(wall);
+            }
+        }
+    }
 }
 
-fn all_moves() -> impl Iterator<Item = Move> {
-    let adds_walls = [Orientation::Horizontal, Orientation::Vertical]
+/// Calls `visit` with each square a pawn on `from` can move to when the
+/// `occupied` squares hold other pawns. The same square can come up twice.
+fn pawn_steps<B: Board>(
+    board: &B,
+    from: (u8, u8),
+    occupied: impl Fn((u8, u8)) -> bool,
+    mut visit: impl FnMut((u8, u8)),
+) {
+    let size = board.size();
+    for direction in Direction::ALL.iter() {
+        let step = match direction.shift(from, size) {
+            Some(step) if board.is_passible(from, step) => step,
+            _ => continue,
+        };
+        if !occupied(step) {
+            visit(step);
+            continue;
+        }
+
+        match direction
+            .shift(step, size)
+            .filter(|beyond| board.is_passible(step, *beyond) && !occupied(*beyond))
+        {
+            Some(beyond) => visit(beyond),
+            None => {
+                for side in direction.perpendicular().iter() {
+                    if let Some(diagonal) = side.shift(step, size) {
+                        if board.is_passible(step, diagonal) && !occupied(diagonal) {
+                            visit(diagonal);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn all_walls(size: u8) -> impl Iterator<Item = Move> {
+    [Orientation::Horizontal, Orientation::Vertical]
         .iter()
-        .map(|x| *x)
-        .flat_map(|o| {
-            (0..8).flat_map(move |y| {
-                (0..8).map(move |x| Move::AddWall {
+        .copied()
+        .flat_map(move |o| {
+            (0..size - 1).flat_map(move |y| {
+                (0..size - 1).map(move |x| Move::AddWall {
                     orientation: o,
                     location: (x, y),
                 })
             })
-        });
-
-    let locations = (0..9).flat_map(move |x| (0..9).map(move |y| Move::MoveTo(x, y)));
-
-    locations.chain(adds_walls)
+        })
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
@@ -148,31 +457,119 @@ pub enum Direction {
     Right,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, FromStr, Display)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, FromStr, Display, Serialize, Deserialize)]
 #[display(style = "kebab-case")]
 pub enum Player {
     Player1,
     Player2,
+    Player3,
+    Player4,
+}
+
+const TWO_PLAYER_SEATS: [Player; 2] = [Player::Player1, Player::Player2];
+const FOUR_PLAYER_SEATS: [Player; 4] = [
+    Player::Player1,
+    Player::Player3,
+    Player::Player2,
+    Player::Player4,
+];
+
+pub fn is_valid_player_count(players: u8) -> bool {
+    players == 2 || players == 4
 }
+
 impl Player {
+    pub const ALL: [Player; 4] = [
+        Player::Player1,
+        Player::Player2,
+        Player::Player3,
+        Player::Player4,
+    ];
+
+    /// The player on the opposite side of the board.
     pub fn other(&self) -> Player {
         match self {
             Player::Player1 => Player::Player2,
             Player::Player2 => Player::Player1,
+            Player::Player3 => Player::Player4,
+            Player::Player4 => Player::Player3,
+        }
+    }
+
+    /// Seats in turn order. Player 1 starts at the top edge and player 2 at the
+    /// bottom; in a four player game player 3 starts on the right edge and
+    /// player 4 on the left, so play goes clockwise around the board.
+    pub fn seats(players: u8) -> &'static [Player] {
+        match players {
+            4 => &FOUR_PLAYER_SEATS,
+            _ => &TWO_PLAYER_SEATS,
+        }
+    }
+
+    /// The player whose turn comes after this one.
+    pub fn next(&self, players: u8) -> Player {
+        let seats = Player::seats(players);
+        let turn = seats.iter().position(|p| p == self).unwrap_or(0);
+        seats[(turn + 1) % seats.len()]
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            Player::Player1 => 0,
+            Player::Player2 => 1,
+            Player::Player3 => 2,
+            Player::Player4 => 3,
+        }
+    }
+
+    pub fn start(&self, size: u8) -> (u8, u8) {
+        let (middle, last) = (size / 2, size - 1);
+        match self {
+            Player::Player1 => (middle, 0),
+            Player::Player2 => (middle, last),
+            Player::Player3 => (last, middle),
+            Player::Player4 => (0, middle),
+        }
+    }
+
+    /// Number of rows (or columns) between `location` and this player's goal
+    /// edge, ignoring walls.
+    pub fn goal_distance(&self, size: u8, (x, y): (u8, u8)) -> u8 {
+        let last = size - 1;
+        match self {
+            Player::Player1 => last - y,
+            Player::Player2 => y,
+            Player::Player3 => x,
+            Player::Player4 => last - x,
         }
     }
 }
 impl Direction {
-    pub fn shift(&self, position: (u8, u8)) -> Option<(u8, u8)> {
-        fn add((ax, ay): &(u8, u8), (bx, by): &(i8, i8)) -> Option<(u8, u8)> {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    pub fn perpendicular(&self) -> [Direction; 2] {
+        match self {
+            Direction::Up | Direction::Down => [Direction::Left, Direction::Right],
+            Direction::Left | Direction::Right => [Direction::Up, Direction::Down],
+        }
+    }
+
+    pub fn shift(&self, position: (u8, u8), size: u8) -> Option<(u8, u8)> {
+        let add = |(ax, ay): &(u8, u8), (bx, by): &(i8, i8)| -> Option<(u8, u8)> {
             let res = (*ax as i8 + bx, *ay as i8 + by);
+            let size = size as i8;
 
-            if res.0 >= 0 && res.0 < 9 && res.1 >= 0 && res.1 < 9 {
+            if res.0 >= 0 && res.0 < size && res.1 >= 0 && res.1 < size {
                 Some((res.0 as u8, res.1 as u8))
             } else {
                 None
             }
-        }
+        };
 
         match self {
             Direction::Up => add(&position, &(0, -1)),
@@ -190,3 +587,354 @@ pub enum Move {
     },
     MoveTo(u8, u8),
 }
+
+/// A move that has been made on a board, with what is needed to undo it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMove {
+    pub player: Player,
+    pub mov: Move,
+    pub from: (u8, u8),
+}
+
+/// The distance from each square to one player's goal, as filled in by
+/// `Board::distance_field`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceField {
+    size: u8,
+    distances: [[u8; MAX_SIZE as usize]; MAX_SIZE as usize],
+}
+
+impl DistanceField {
+    pub fn unreachable(size: u8) -> Self {
+        Self {
+            size,
+            distances: [[u8::MAX; MAX_SIZE as usize]; MAX_SIZE as usize],
+        }
+    }
+
+    pub fn size(&self) -> u8 {
+        self.size
+    }
+
+    /// `None` for squares that are off the board or cut off from the goal.
+    pub fn get(&self, (x, y): (u8, u8)) -> Option<u8> {
+        if x >= self.size || y >= self.size {
+            return None;
+        }
+        Some(self.distances[x as usize][y as usize]).filter(|d| *d != u8::MAX)
+    }
+
+    pub fn set(&mut self, (x, y): (u8, u8), distance: u8) {
+        self.distances[x as usize][y as usize] = distance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitpacked::BoardV2, v1::BoardV1};
+
+    const H: Orientation = Orientation::Horizontal;
+    const V: Orientation = Orientation::Vertical;
+
+    fn check_pawn_moves_on<B: Board>(
+        pawns: &[(Player, (u8, u8))],
+        walls: &[((u8, u8), Orientation)],
+        expected: &[(u8, u8)],
+    ) {
+        let mut board = B::with_players(9, if pawns.len() > 2 { 4 } else { 2 }).unwrap();
+        for (player, location) in pawns {
+            board.move_token(*player, *location).unwrap();
+        }
+        for (location, orientation) in walls {
+            board
+                .add_wall(Player::Player2, *location, *orientation)
+                .unwrap();
+        }
+
+        let mut moves = board.pawn_moves(Player::Player1);
+        moves.sort_unstable();
+        let mut expected = expected.to_vec();
+        expected.sort_unstable();
+        assert_eq!(moves, expected);
+
+        for x in 0..9 {
+            for y in 0..9 {
+                let result = board.check_move(Player::Player1, &Move::MoveTo(x, y));
+                if expected.contains(&(x, y)) {
+                    assert_eq!(result, Ok(()), "({}, {})", x, y);
+                } else {
+                    assert_eq!(result, Err(IllegalMove::IllegalPawnMove), "({}, {})", x, y);
+                }
+            }
+        }
+        let legal_pawn_moves = board
+            .legal_moves(Player::Player1)
+            .into_iter()
+            .filter(|mov| matches!(mov, Move::MoveTo(..)))
+            .count();
+        assert_eq!(legal_pawn_moves, expected.len());
+    }
+
+    fn check_pawn_moves(
+        pawns: &[(Player, (u8, u8))],
+        walls: &[((u8, u8), Orientation)],
+        expected: &[(u8, u8)],
+    ) {
+        check_pawn_moves_on::<BoardV1>(pawns, walls, expected);
+        check_pawn_moves_on::<BoardV2>(pawns, walls, expected);
+    }
+
+    #[test]
+    fn steps() {
+        use Player::*;
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (0, 8))],
+            &[],
+            &[(4, 3), (4, 5), (3, 4), (5, 4)],
+        );
+        check_pawn_moves(
+            &[(Player1, (0, 0)), (Player2, (8, 8))],
+            &[],
+            &[(1, 0), (0, 1)],
+        );
+        check_pawn_moves(
+            &[(Player1, (8, 8)), (Player2, (0, 0))],
+            &[],
+            &[(7, 8), (8, 7)],
+        );
+        check_pawn_moves(
+            &[(Player1, (4, 0)), (Player2, (4, 8))],
+            &[],
+            &[(3, 0), (5, 0), (4, 1)],
+        );
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (0, 8))],
+            &[((4, 4), H), ((3, 3), V)],
+            &[(4, 3), (5, 4)],
+        );
+    }
+
+    #[test]
+    fn straight_jumps() {
+        use Player::*;
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (4, 5))],
+            &[],
+            &[(4, 6), (4, 3), (3, 4), (5, 4)],
+        );
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (4, 3))],
+            &[],
+            &[(4, 2), (4, 5), (3, 4), (5, 4)],
+        );
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (3, 4))],
+            &[],
+            &[(2, 4), (4, 3), (4, 5), (5, 4)],
+        );
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (5, 4))],
+            &[],
+            &[(6, 4), (4, 3), (4, 5), (3, 4)],
+        );
+    }
+
+    #[test]
+    fn no_jump_through_walls() {
+        use Player::*;
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (4, 5))],
+            &[((3, 4), H)],
+            &[(4, 3), (3, 4), (5, 4)],
+        );
+        check_pawn_moves(
+            &[(Player1, (1, 0)), (Player2, (0, 0))],
+            &[((0, 0), V)],
+            &[(2, 0), (1, 1)],
+        );
+    }
+
+    #[test]
+    fn diagonal_jumps_when_wall_behind() {
+        use Player::*;
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (4, 5))],
+            &[((4, 5), H)],
+            &[(3, 5), (5, 5), (4, 3), (3, 4), (5, 4)],
+        );
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (3, 4))],
+            &[((2, 4), V)],
+            &[(3, 3), (3, 5), (4, 3), (4, 5), (5, 4)],
+        );
+        // one side of the diagonal is walled off too
+        check_pawn_moves(
+            &[(Player1, (4, 4)), (Player2, (4, 5))],
+            &[((4, 5), H), ((4, 4), V)],
+            &[(3, 5), (4, 3), (3, 4)],
+        );
+    }
+
+    #[test]
+    fn diagonal_jumps_at_edges() {
+        use Player::*;
+        check_pawn_moves(
+            &[(Player1, (4, 7)), (Player2, (4, 8))],
+            &[],
+            &[(3, 8), (5, 8), (4, 6), (3, 7), (5, 7)],
+        );
+        check_pawn_moves(
+            &[(Player1, (4, 7)), (Player2, (4, 8))],
+            &[((4, 7), V)],
+            &[(3, 8), (4, 6), (3, 7)],
+        );
+        // opponent in the corner: only one diagonal stays on the board
+        check_pawn_moves(
+            &[(Player1, (1, 0)), (Player2, (0, 0))],
+            &[],
+            &[(0, 1), (2, 0), (1, 1)],
+        );
+        check_pawn_moves(
+            &[(Player1, (8, 7)), (Player2, (8, 8))],
+            &[],
+            &[(7, 8), (8, 6), (7, 7)],
+        );
+    }
+
+    #[test]
+    fn jumps_with_four_players() {
+        use Player::*;
+        // a pawn behind the one being jumped forces a diagonal jump, and
+        // diagonal squares holding a pawn are skipped
+        check_pawn_moves(
+            &[
+                (Player1, (4, 4)),
+                (Player3, (4, 5)),
+                (Player2, (4, 6)),
+                (Player4, (5, 5)),
+            ],
+            &[],
+            &[(3, 5), (4, 3), (3, 4), (5, 4)],
+        );
+        // two neighbours that share a diagonal square only list it once
+        check_pawn_moves(
+            &[
+                (Player1, (0, 0)),
+                (Player3, (1, 0)),
+                (Player2, (0, 1)),
+                (Player4, (2, 0)),
+            ],
+            &[((0, 1), H)],
+            &[(1, 1)],
+        );
+    }
+
+    fn distance_fields_on<B: Board>() -> Vec<DistanceField> {
+        let mut board = B::with_players(9, 4).unwrap();
+        for (location, orientation) in &[((3, 0), H), ((4, 2), V), ((0, 4), H), ((6, 6), V)] {
+            board
+                .add_wall(Player::Player2, *location, *orientation)
+                .unwrap();
+        }
+
+        let mut fields = vec![];
+        for player in board.players() {
+            let field = board.distance_field(*player);
+            let start = board.player_location(*player);
+            assert_eq!(board.distance_to_goal(*player), field.get(start));
+            assert_eq!(None, field.get((9, 0)));
+
+            let path = board.shortest_path(*player).unwrap();
+            assert_eq!(field.get(start), Some(path.len() as u8));
+            assert_eq!(Some(0), field.get(*path.last().unwrap()));
+            let mut at = start;
+            for step in path {
+                assert!(board.is_passible(at, step), "{:?} to {:?}", at, step);
+                at = step;
+            }
+            fields.push(field);
+        }
+        fields
+    }
+
+    #[test]
+    fn paths_and_distance_fields() {
+        assert_eq!(
+            distance_fields_on::<BoardV1>(),
+            distance_fields_on::<BoardV2>()
+        );
+        assert_eq!(
+            Some(vec![(2, 1), (2, 2), (2, 3), (2, 4)]),
+            BoardV2::with_size(5)
+                .unwrap()
+                .shortest_path(Player::Player1)
+        );
+    }
+
+    fn pawn_distances_on<B: Board>(walls: &[((u8, u8), Orientation)]) -> [Option<u8>; 2] {
+        let mut board = B::with_size(9).unwrap();
+        board.move_token(Player::Player1, (4, 6)).unwrap();
+        board.move_token(Player::Player2, (4, 7)).unwrap();
+        for (location, orientation) in walls {
+            board
+                .add_wall(Player::Player1, *location, *orientation)
+                .unwrap();
+        }
+        let distances = [
+            board.pawn_distance_to_goal(Player::Player1),
+            board.pawn_distance_to_goal(Player::Player2),
+        ];
+        assert_eq!(
+            board
+                .race_margin(Player::Player1, Player::Player1)
+                .map(|margin| -margin),
+            board.race_margin(Player::Player2, Player::Player1)
+        );
+        distances
+    }
+
+    #[test]
+    fn pawn_aware_distances() {
+        // face to face in the open, both can jump
+        let open = [Some(1), Some(6)];
+        assert_eq!(open, pawn_distances_on::<BoardV1>(&[]));
+        assert_eq!(open, pawn_distances_on::<BoardV2>(&[]));
+
+        // player 2 corks the corridor player 1 has to leave by
+        let corridor = [((3, 6), V), ((4, 6), V), ((4, 7), H)];
+        let blocked = [Some(5), Some(6)];
+        assert_eq!(blocked, pawn_distances_on::<BoardV1>(&corridor));
+        assert_eq!(blocked, pawn_distances_on::<BoardV2>(&corridor));
+
+        let mut board = BoardV2::with_size(9).unwrap();
+        assert_eq!(Some(1), board.race_margin(Player::Player1, Player::Player1));
+        assert_eq!(
+            Some(-1),
+            board.race_margin(Player::Player1, Player::Player2)
+        );
+        board.move_token(Player::Player1, (4, 6)).unwrap();
+        board.move_token(Player::Player2, (4, 7)).unwrap();
+        assert_eq!(
+            Some(11),
+            board.race_margin(Player::Player1, Player::Player1)
+        );
+        for (location, orientation) in &corridor {
+            board
+                .add_wall(Player::Player1, *location, *orientation)
+                .unwrap();
+        }
+        assert_eq!(Some(3), board.race_margin(Player::Player1, Player::Player1));
+
+        let board = BoardV2::with_players(9, 4).unwrap();
+        assert_eq!(Some(1), board.race_margin(Player::Player1, Player::Player1));
+        assert_eq!(
+            Some(-1),
+            board.race_margin(Player::Player3, Player::Player1)
+        );
+        assert_eq!(
+            Some(-3),
+            board.race_margin(Player::Player4, Player::Player1)
+        );
+    }
+}
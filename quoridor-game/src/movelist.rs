@@ -0,0 +1,148 @@
+use crate::{Move, MAX_SIZE};
+use core::ops::Deref;
+
+/// A fixed-capacity list with room for every legal move on the largest
+/// board, so move generation never allocates.
+#[derive(Clone, Debug)]
+pub struct MoveList {
+    moves: [Move; MoveList::CAPACITY],
+    len: usize,
+}
+
+const EMPTY: Move = Move::MoveTo(0, 0);
+
+impl MoveList {
+    /// Up to six pawn moves plus a wall in each orientation at every joint.
+    /// A pawn reaches all four diagonal squares only by jumping the pawns on
+    /// two of its sides, which leaves one move each way along the other two.
+    pub const CAPACITY: usize = 6 + 2 * (MAX_SIZE as usize - 1) * (MAX_SIZE as usize - 1);
+
+    pub fn new() -> Self {
+        Self {
+            moves: [EMPTY; MoveList::CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Panics if the list is full.
+    pub fn push(&mut self, mov: Move) {
+        self.moves[self.len] = mov;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn as_slice(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        self.as_slice()
+    }
+}
+
+impl PartialEq for MoveList {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for MoveList {}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = core::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter {
+            list: self,
+            next: 0,
+        }
+    }
+}
+
+pub struct IntoIter {
+    list: MoveList,
+    next: usize,
+}
+
+impl Iterator for IntoIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        if self.next == self.list.len {
+            return None;
+        }
+        self.next += 1;
+        Some(core::mem::replace(
+            &mut self.list.moves[self.next - 1],
+            EMPTY,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.list.len - self.next;
+        (left, Some(left))
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitpacked::BoardV2, v1::BoardV1, Board, Player};
+
+    #[test]
+    fn holds_every_move_on_the_largest_board() {
+        let board = BoardV1::with_size(MAX_SIZE).unwrap();
+        let mut moves = MoveList::new();
+        board.generate_moves(Player::Player1, &mut moves);
+        assert_eq!(3 + 2 * 10 * 10, moves.len());
+
+        moves.clear();
+        BoardV2::empty().generate_moves(Player::Player2, &mut moves);
+        assert_eq!(
+            BoardV2::empty().legal_moves(Player::Player2),
+            moves.to_vec()
+        );
+        assert_eq!(moves.len(), moves.clone().into_iter().count());
+        assert_eq!(Some(Move::MoveTo(4, 7)), moves.into_iter().next());
+    }
+
+    #[test]
+    fn holds_six_pawn_moves() {
+        // player 1 jumps diagonally past player 4 at the edge and player 3,
+        // which has player 2 behind it
+        let mut board = BoardV1::with_players(MAX_SIZE, 4).unwrap();
+        *board.location_mut(&Player::Player1) = (1, 5);
+        *board.location_mut(&Player::Player4) = (0, 5);
+        *board.location_mut(&Player::Player3) = (2, 5);
+        *board.location_mut(&Player::Player2) = (3, 5);
+        let mut moves = MoveList::new();
+        board.generate_moves(Player::Player1, &mut moves);
+        assert_eq!(MoveList::CAPACITY, moves.len());
+        assert_eq!(6, board.pawn_moves(Player::Player1).len());
+    }
+}
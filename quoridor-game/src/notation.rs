@@ -0,0 +1,209 @@
+//! Algebraic notation for moves. Columns are lettered from `a` at x = 0 and
+//! rows numbered from `1` at y = 0, so player 1 starts on `e1` of a 9x9 board.
+//! A pawn move names the square it lands on (`e2`); a wall names the square
+//! just below and left of its centre followed by `h` or `v` (`e3h` is
+//! `AddWall { location: (4, 2), orientation: Horizontal }`).
+
+use crate::{Move, Orientation, Player};
+use core::{fmt, str::FromStr};
+use parse_display::Display;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display)]
+#[display("invalid move notation")]
+pub struct ParseMoveError;
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseMoveError {}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let square = |f: &mut fmt::Formatter<'_>, (x, y): (u8, u8)| {
+            write!(f, "{}{}", (b'a' + x) as char, y as u16 + 1)
+        };
+        match self {
+            Move::MoveTo(x, y) => square(f, (*x, *y)),
+            Move::AddWall {
+                location,
+                orientation,
+            } => {
+                square(f, *location)?;
+                match orientation {
+                    Orientation::Horizontal => write!(f, "h"),
+                    Orientation::Vertical => write!(f, "v"),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let mut chars = s.chars();
+        let x = match chars.next() {
+            Some(c @ 'a'..='z') => c as u8 - b'a',
+            _ => return Err(ParseMoveError),
+        };
+        let rest = chars.as_str();
+        let (row, orientation) = match rest.char_indices().last() {
+            Some((i, 'h')) => (&rest[..i], Some(Orientation::Horizontal)),
+            Some((i, 'v')) => (&rest[..i], Some(Orientation::Vertical)),
+            _ => (rest, None),
+        };
+        if !row.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ParseMoveError);
+        }
+        let y = row
+            .parse::<u8>()
+            .ok()
+            .and_then(|row| row.checked_sub(1))
+            .ok_or(ParseMoveError)?;
+
+        Ok(match orientation {
+            Some(orientation) => Move::AddWall {
+                location: (x, y),
+                orientation,
+            },
+            None => Move::MoveTo(x, y),
+        })
+    }
+}
+
+impl Move {
+    /// Rewrites a move written from `player`'s side of the board, as if they
+    /// were player 1 starting on row 1, into board coordinates.
+    pub fn from_perspective(&self, player: Player, size: u8) -> Move {
+        let seen_by = match player {
+            Player::Player3 => Player::Player4,
+            Player::Player4 => Player::Player3,
+            player => player,
+        };
+        self.to_perspective(seen_by, size)
+    }
+
+    /// Rewrites a move so it reads as if `player` were player 1, sitting on
+    /// row 1 and moving up the board.
+    pub fn to_perspective(&self, player: Player, size: u8) -> Move {
+        let last = size - 1;
+        match self {
+            Move::MoveTo(x, y) => {
+                let (x, y) = match player {
+                    Player::Player1 => (*x, *y),
+                    Player::Player2 => (last - x, last - y),
+                    Player::Player3 => (*y, last - x),
+                    Player::Player4 => (last - y, *x),
+                };
+                Move::MoveTo(x, y)
+            }
+            Move::AddWall {
+                location: (x, y),
+                orientation,
+            } => {
+                let last = last - 1;
+                let (location, orientation) = match player {
+                    Player::Player1 => ((*x, *y), *orientation),
+                    Player::Player2 => ((last - x, last - y), *orientation),
+                    Player::Player3 => ((*y, last - x), orientation.other()),
+                    Player::Player4 => ((last - y, *x), orientation.other()),
+                };
+                Move::AddWall {
+                    location,
+                    orientation,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitpacked::BoardV2, Board};
+
+    #[test]
+    fn parses_and_prints_notation() {
+        assert_eq!(Ok(Move::MoveTo(4, 0)), "e1".parse());
+        assert_eq!(Ok(Move::MoveTo(4, 1)), "E2".parse());
+        assert_eq!(
+            Ok(Move::AddWall {
+                location: (4, 2),
+                orientation: Orientation::Horizontal
+            }),
+            "e3h".parse()
+        );
+        assert_eq!(
+            Ok(Move::AddWall {
+                location: (3, 6),
+                orientation: Orientation::Vertical
+            }),
+            "d7v".parse()
+        );
+        assert_eq!(Ok(Move::MoveTo(10, 10)), "k11".parse());
+        for bad in &[
+            "", "e", "e0", "1e", "e1x", "ee1", "e-1", "eh", "e1hv", "e256",
+        ] {
+            assert_eq!(Err(ParseMoveError), bad.parse::<Move>(), "{}", bad);
+        }
+
+        let board = BoardV2::empty();
+        for mov in board
+            .legal_moves(Player::Player1)
+            .into_iter()
+            .chain(board.legal_moves(Player::Player2))
+        {
+            assert_eq!(Ok(mov.clone()), mov.to_string().parse(), "{}", mov);
+        }
+    }
+
+    #[test]
+    fn perspectives() {
+        let size = 9;
+        let board = BoardV2::with_players(size, 4).unwrap();
+        for player in board.players() {
+            let (x, y) = player.start(size);
+            assert_eq!(
+                "e1",
+                Move::MoveTo(x, y).to_perspective(*player, size).to_string()
+            );
+            for mov in board.legal_moves(*player) {
+                let seen = mov.to_perspective(*player, size);
+                assert_eq!(mov, seen.from_perspective(*player, size));
+            }
+        }
+
+        // one step forward reads as e2 from every seat
+        assert_eq!(
+            Move::MoveTo(7, 4),
+            "e2".parse::<Move>()
+                .unwrap()
+                .from_perspective(Player::Player3, size)
+        );
+        assert_eq!(
+            Move::MoveTo(4, 7),
+            "e2".parse::<Move>()
+                .unwrap()
+                .from_perspective(Player::Player2, size)
+        );
+        // walls turn with the board
+        assert_eq!(
+            "e8h",
+            Move::AddWall {
+                location: (3, 0),
+                orientation: Orientation::Horizontal
+            }
+            .to_perspective(Player::Player2, size)
+            .to_string()
+        );
+        assert_eq!(
+            "h4v",
+            Move::AddWall {
+                location: (3, 0),
+                orientation: Orientation::Horizontal
+            }
+            .to_perspective(Player::Player4, size)
+            .to_string()
+        );
+    }
+}
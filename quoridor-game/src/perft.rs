@@ -0,0 +1,118 @@
+//! Perft: counting every sequence of legal moves of a given length. The
+//! counts for a position don't depend on how moves are generated, so any
+//! change to pawn jumps, wall placement or `is_legal` can be checked against
+//! the [`REFERENCE`] table.
+
+use crate::{Board, GameRecord, Move, MoveList};
+use alloc::{vec, vec::Vec};
+
+/// Known counts for a few positions, as a position string (see
+/// [`crate::fen`]) and the counts for depth 1, 2, and so on. The deepest
+/// counts take too long for the regular tests; check them with
+/// `cargo test --release -p quoridor-game -- --ignored`.
+pub const REFERENCE: &[(&str, &[u64])] = &[
+    // the start
+    ("- - e1,e9 10,10 1", &[131, 16677, 2062264, 247569030]),
+    ("- - c1,c5 3,3 1 5", &[35, 1109, 31540, 794442]),
+    ("- - c1,c5,e3,a3 2,2,2,2 1 5", &[35, 1109, 31365, 776775]),
+    // face to face, with jumps both ways
+    ("- - e5,e6 10,10 1", &[132, 16938, 2111842, 255718700]),
+    // the straight jump runs off the board, so both diagonals win
+    ("- - e8,e9 0,0 1", &[5, 9, 38, 96]),
+    ("d8 - e8,e9 1,0 2", &[2, 254, 631, 3741]),
+    // one gap left in a row of walls, so most walls would cut someone off
+    (
+        "a4,c4,e4,g4 - e1,e9 6,6 1",
+        &[119, 13711, 1528506, 164725402],
+    ),
+    // already won
+    ("- - e9,e8 10,10 2", &[0, 0, 0, 0]),
+];
+
+/// The number of move sequences `depth` moves long from the current
+/// position. A game that is over has no moves, so it only counts at depth 0.
+pub fn perft<B: Board + Clone>(game: &mut GameRecord<B>, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = MoveList::new();
+    game.generate_moves(&mut moves);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut count = 0;
+    for mov in &moves {
+        game.apply_move(mov).expect("generated moves are legal");
+        count += perft(game, depth - 1);
+        game.undo();
+    }
+    count
+}
+
+/// Splits `perft` up by the first move, in the order moves are generated.
+pub fn divide<B: Board + Clone>(game: &mut GameRecord<B>, depth: u8) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+    let mut moves = MoveList::new();
+    game.generate_moves(&mut moves);
+    moves
+        .into_iter()
+        .map(|mov| {
+            game.apply_move(&mov).expect("generated moves are legal");
+            let count = perft(game, depth - 1);
+            game.undo();
+            (mov, count)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitpacked::BoardV2, v1::BoardV1};
+
+    fn check_reference<B: Board + Clone>(max_count: u64) {
+        for (fen, counts) in REFERENCE {
+            let mut game = GameRecord::<B>::from_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate() {
+                if *count <= max_count {
+                    assert_eq!(*count, perft(&mut game, depth as u8 + 1), "{}", fen);
+                }
+            }
+            assert_eq!(1, perft(&mut game, 0));
+            assert_eq!(*fen, game.fen());
+        }
+    }
+
+    #[test]
+    fn matches_reference_counts() {
+        check_reference::<BoardV2>(2_500_000);
+        check_reference::<BoardV1>(20_000);
+    }
+
+    #[test]
+    #[ignore]
+    fn matches_deep_reference_counts() {
+        check_reference::<BoardV2>(u64::MAX);
+        check_reference::<BoardV1>(2_500_000);
+    }
+
+    #[test]
+    fn divides_by_first_move() {
+        let mut game = GameRecord::<BoardV2>::from_fen("- - e8,e9 0,0 1").unwrap();
+        let mut split = divide(&mut game, 2)
+            .iter()
+            .map(|(mov, count)| format!("{} {}", mov, count))
+            .collect::<Vec<_>>();
+        split.sort();
+        assert_eq!(vec!["d8 3", "d9 0", "e7 3", "f8 3", "f9 0"], split);
+
+        let mut game = GameRecord::<BoardV2>::from_fen("- - c1,c5 3,3 1 5").unwrap();
+        let split = divide(&mut game, 3);
+        assert_eq!(35, split.len());
+        assert_eq!(31540, split.iter().map(|(_, count)| count).sum::<u64>());
+        assert!(divide(&mut game, 0).is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+//! A PGN-style text format for whole games: `[Name "value"]` header tags
+//! followed by a numbered move list in algebraic notation.
+//!
+//! ```text
+//! [Player1 "keyboard"]
+//! [Player2 "greedy-ai"]
+//! [Size "9"]
+//! [Players "2"]
+//! [Result "player1"]
+//!
+//! 1. e2 e8 2. e3 e7 3. e3h ...
+//! ```
+
+use crate::{bitpacked::BoardV2, fen, Board, GameRecord, IllegalMove, Move, Player};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+use parse_display::Display;
+
+/// Tags that describe the game itself. They are written from the game rather
+/// than from the stored tags.
+const GAME_TAGS: [&str; 4] = ["Size", "Players", "Position", "Result"];
+
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+pub enum PgnError {
+    #[display("malformed tag on line {0}")]
+    BadTag(usize),
+    #[display("bad value for the {0} tag")]
+    BadTagValue(String),
+    #[display("can't read move {0:?}")]
+    BadMove(String),
+    #[display("move {ply} ({mov}) is illegal: {reason}")]
+    IllegalMove {
+        ply: usize,
+        mov: Move,
+        reason: IllegalMove,
+    },
+    #[display("move {0} comes after the game was won")]
+    MoveAfterResult(usize),
+    #[display("the moves don't lead to the recorded result")]
+    ResultMismatch,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PgnError {}
+
+#[derive(Clone, Debug)]
+pub struct GameFile<B = BoardV2> {
+    tags: Vec<(String, String)>,
+    game: GameRecord<B>,
+}
+
+impl<B: Board> GameFile<B> {
+    pub fn new(game: GameRecord<B>) -> Self {
+        Self { tags: vec![], game }
+    }
+
+    pub fn game(&self) -> &GameRecord<B> {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut GameRecord<B> {
+        &mut self.game
+    }
+
+    pub fn into_game(self) -> GameRecord<B> {
+        self.game
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags().find(|(k, _)| *k == name).map(|(_, v)| v)
+    }
+
+    /// Sets a free-form tag such as `Player1`, `Date` or an engine setting.
+    /// Tags describing the game itself (size, position, result) come from the
+    /// game and are ignored here.
+    pub fn set_tag(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let (name, value) = (name.into(), value.into());
+        if GAME_TAGS.contains(&name.as_str()) {
+            return;
+        }
+        match self.tags.iter_mut().find(|(k, _)| *k == name) {
+            Some((_, v)) => *v = value,
+            None => self.tags.push((name, value)),
+        }
+    }
+}
+
+fn write_tag(f: &mut fmt::Formatter<'_>, name: &str, value: impl fmt::Display) -> fmt::Result {
+    let value = value.to_string();
+    writeln!(
+        f,
+        "[{} \"{}\"]",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+impl<B: Board + Clone + PartialEq> fmt::Display for GameFile<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.tags() {
+            write_tag(f, name, value)?;
+        }
+        let start = self.game.start();
+        write_tag(f, "Size", start.size())?;
+        write_tag(f, "Players", start.num_players())?;
+        let standard_start =
+            B::with_players(start.size(), start.num_players()).is_some_and(|board| board == *start);
+        if !standard_start || self.game.start_player() != Player::Player1 {
+            write_tag(f, "Position", fen::to_fen(start, self.game.start_player()))?;
+        }
+        match self.game.winner() {
+            Some(winner) => write_tag(f, "Result", winner)?,
+            None => write_tag(f, "Result", "*")?,
+        }
+        writeln!(f)?;
+
+        let players = start.num_players() as usize;
+        let mut line = String::new();
+        for (ply, mov) in self.game.moves().enumerate() {
+            let token = if ply % players == 0 {
+                format!("{}. {}", ply / players + 1, mov)
+            } else {
+                mov.to_string()
+            };
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        if !line.is_empty() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            '"' => return None,
+            c => unescaped.push(c),
+        }
+    }
+    Some((name.to_string(), unescaped))
+}
+
+impl<B: Board + Clone> FromStr for GameFile<B> {
+    type Err = PgnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tags = vec![];
+        let mut movetext = String::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') && movetext.is_empty() {
+                tags.push(parse_tag(line).ok_or(PgnError::BadTag(number + 1))?);
+            } else {
+                movetext.push_str(line);
+                movetext.push(' ');
+            }
+        }
+
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v): &(String, String)| v.as_str())
+        };
+        let bad_value = |name: &str| PgnError::BadTagValue(name.to_string());
+        let parse_tag_as = |name: &str, default: u8| -> Result<u8, PgnError> {
+            tag(name).map_or(Ok(default), |v| v.parse().map_err(|_| bad_value(name)))
+        };
+
+        let (board, to_move) = match tag("Position") {
+            Some(position) => fen::from_fen(position).map_err(|_| bad_value("Position"))?,
+            None => (
+                B::with_players(
+                    parse_tag_as("Size", crate::DEFAULT_SIZE)?,
+                    parse_tag_as("Players", 2)?,
+                )
+                .ok_or_else(|| bad_value("Size"))?,
+                Player::Player1,
+            ),
+        };
+        let result = match tag("Result") {
+            None | Some("*") => None,
+            Some(winner) => Some(winner.parse::<Player>().map_err(|_| bad_value("Result"))?),
+        };
+
+        let mut game = GameRecord::from_position(board, to_move);
+        for token in movetext.split_whitespace() {
+            // move numbers may be written apart from or glued to the move
+            let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+            if token.is_empty() {
+                continue;
+            }
+            let ply = game.ply() + 1;
+            if game.winner().is_some() {
+                return Err(PgnError::MoveAfterResult(ply));
+            }
+            let mov: Move = token
+                .parse()
+                .map_err(|_| PgnError::BadMove(token.to_string()))?;
+            game.play(&mov)
+                .map_err(|reason| PgnError::IllegalMove { ply, mov, reason })?;
+        }
+
+        if result.is_some() && result != game.winner() {
+            return Err(PgnError::ResultMismatch);
+        }
+
+        tags.retain(|(k, _)| !GAME_TAGS.contains(&k.as_str()));
+        Ok(GameFile { tags, game })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{v1::BoardV1, Orientation};
+
+    const GAME: &str = r#"[Player1 "keyboard"]
+[Player2 "mcts-ai-1000 \"fast\""]
+[Date "2021.05.01"]
+[Size "5"]
+[Players "2"]
+[Result "player1"]
+
+1. c2 c4 2. a1h c3 3. b2 d3 4. b3 c3 5. b4 c4 6. b5
+"#;
+
+    #[test]
+    fn round_trips_a_finished_game() {
+        let file: GameFile = GAME.parse().unwrap();
+        assert_eq!(Some("mcts-ai-1000 \"fast\""), file.tag("Player2"));
+        assert_eq!(11, file.game().ply());
+        assert_eq!(Some(Player::Player1), file.game().winner());
+        assert_eq!(
+            Some(&Move::AddWall {
+                location: (0, 0),
+                orientation: Orientation::Horizontal
+            }),
+            file.game().moves().nth(2)
+        );
+        assert_eq!(GAME, file.to_string());
+    }
+
+    #[test]
+    fn reads_loose_movetext() {
+        let file: GameFile = "1.e2 e8\n2.e3\n\n   e7 3. e4".parse().unwrap();
+        assert_eq!(5, file.game().ply());
+        assert_eq!(Player::Player2, file.game().current_player());
+        assert_eq!((4, 3), file.game().board().player_location(Player::Player1));
+        assert!(file.to_string().contains("[Result \"*\"]"));
+    }
+
+    #[test]
+    fn round_trips_other_starting_positions() {
+        let mut board = BoardV2::with_players(7, 4).unwrap();
+        board.move_token(Player::Player3, (5, 3)).unwrap();
+        let mut game = GameRecord::from_position(board, Player::Player2);
+        game.play(&"d6".parse().unwrap()).unwrap();
+        game.play(&"b4".parse().unwrap()).unwrap();
+        let mut file = GameFile::new(game);
+        file.set_tag("Event", "test");
+        file.set_tag("Result", "player4");
+
+        let text = file.to_string();
+        let read: GameFile = text.parse().unwrap();
+        assert_eq!(file.game(), read.game());
+        assert_eq!(Some("test"), read.tag("Event"));
+        assert_eq!(text, read.to_string());
+    }
+
+    #[test]
+    fn reads_games_on_any_board() {
+        let text = "[Size \"11\"]\n\n1. f2 f10 2. f3\n";
+        let file: GameFile<BoardV1> = text.parse().unwrap();
+        assert_eq!((5, 2), file.game().board().player_location(Player::Player1));
+        assert!(file.to_string().contains("[Size \"11\"]"));
+        assert_eq!(
+            Err(PgnError::BadTagValue("Size".to_string())),
+            text.parse::<GameFile>().map(|_| ())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_games() {
+        assert_eq!(
+            Err(PgnError::IllegalMove {
+                ply: 2,
+                mov: Move::MoveTo(4, 0),
+                reason: IllegalMove::IllegalPawnMove
+            }),
+            "1. e2 e1".parse::<GameFile>().map(|_| ())
+        );
+        assert_eq!(
+            Err(PgnError::BadMove("z".to_string())),
+            "1. e2 z".parse::<GameFile>().map(|_| ())
+        );
+        assert_eq!(
+            Err(PgnError::BadTag(2)),
+            "[Size \"5\"]\n[Players 2]\n"
+                .parse::<GameFile>()
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(PgnError::BadTagValue("Size".to_string())),
+            "[Size \"8\"]\n".parse::<GameFile>().map(|_| ())
+        );
+        assert_eq!(
+            Err(PgnError::ResultMismatch),
+            "[Result \"player2\"]\n1. e2"
+                .parse::<GameFile>()
+                .map(|_| ())
+        );
+        let finished = GAME.replace("6. b5", "6. b5 c3");
+        assert_eq!(
+            Err(PgnError::MoveAfterResult(12)),
+            finished.parse::<GameFile>().map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    fen::{self, FenError},
+    AppliedMove, Board, IllegalMove, Move, MoveList, Player,
+};
+use alloc::{string::String, vec::Vec};
+
+/// A game in progress: the position it started from, every move made since
+/// and whose turn it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord<B> {
+    start: B,
+    start_player: Player,
+    board: B,
+    current_player: Player,
+    history: Vec<AppliedMove>,
+}
+
+impl<B: Board + Clone> GameRecord<B> {
+    pub fn new(board: B) -> Self {
+        Self::from_position(board, Player::Player1)
+    }
+
+    pub fn from_position(mut board: B, current_player: Player) -> Self {
+        board.set_side_to_move(current_player);
+        Self {
+            start: board.clone(),
+            start_player: current_player,
+            board,
+            current_player,
+            history: Vec::new(),
+        }
+    }
+
+    /// Starts a game from a position string, see [`crate::fen`].
+    pub fn from_fen(position: &str) -> Result<Self, FenError> {
+        let (board, current_player) = fen::from_fen(position)?;
+        Ok(Self::from_position(board, current_player))
+    }
+
+    /// The current position as a position string.
+    pub fn fen(&self) -> String {
+        fen::to_fen(&self.board, self.current_player)
+    }
+
+    pub fn board(&self) -> &B {
+        &self.board
+    }
+
+    pub fn start(&self) -> &B {
+        &self.start
+    }
+
+    pub fn start_player(&self) -> Player {
+        self.start_player
+    }
+
+    pub fn current_player(&self) -> Player {
+        self.current_player
+    }
+
+    /// `Board::position_key` with the side to move.
+    pub fn position_key(&self) -> u64 {
+        self.board.position_key(self.current_player)
+    }
+
+    pub fn history(&self) -> &[AppliedMove] {
+        &self.history
+    }
+
+    pub fn moves(&self) -> impl Iterator<Item = &Move> {
+        self.history.iter().map(|applied| &applied.mov)
+    }
+
+    pub fn ply(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        self.board.result()
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        self.generate_moves(&mut moves);
+        moves.to_vec()
+    }
+
+    /// Appends the legal moves for the side to move, if the game isn't over.
+    pub fn generate_moves(&self, moves: &mut MoveList) {
+        if self.winner().is_none() {
+            self.board.generate_moves(self.current_player, moves);
+        }
+    }
+
+    pub fn check_move(&self, mov: &Move) -> Result<(), IllegalMove> {
+        self.board.check_move(self.current_player, mov)
+    }
+
+    /// Checks `mov` is legal for the side to move, then plays it.
+    pub fn play(&mut self, mov: &Move) -> Result<(), IllegalMove> {
+        self.check_move(mov)?;
+        self.apply_move(mov)
+    }
+
+    /// Plays `mov` for the side to move without the full legality check, like
+    /// `Board::apply_move`.
+    pub fn apply_move(&mut self, mov: &Move) -> Result<(), IllegalMove> {
+        let applied = self.board.make_move(mov, self.current_player)?;
+        self.history.push(applied);
+        self.current_player = self.current_player.next(self.board.num_players());
+        Ok(())
+    }
+
+    /// Takes back the last move, if there is one.
+    pub fn undo(&mut self) -> Option<AppliedMove> {
+        let applied = self.history.pop()?;
+        self.board.undo_move(&applied);
+        self.current_player = applied.player;
+        Some(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitpacked::BoardV2, Orientation};
+
+    #[test]
+    fn tracks_turns_history_and_result() {
+        let mut game = GameRecord::new(BoardV2::with_size(5).unwrap());
+        assert_eq!(Player::Player1, game.current_player());
+
+        game.play(&Move::MoveTo(2, 1)).unwrap();
+        assert_eq!(Player::Player2, game.current_player());
+        assert_eq!(
+            Err(IllegalMove::IllegalPawnMove),
+            game.play(&Move::MoveTo(0, 0))
+        );
+        game.play(&Move::AddWall {
+            orientation: Orientation::Horizontal,
+            location: (0, 0),
+        })
+        .unwrap();
+        assert_eq!(2, game.ply());
+        assert_eq!(5, game.board().available_walls(Player::Player2));
+
+        game.play(&Move::MoveTo(2, 2)).unwrap();
+        game.play(&Move::MoveTo(2, 3)).unwrap();
+        game.play(&Move::MoveTo(1, 2)).unwrap();
+        game.play(&Move::MoveTo(2, 4)).unwrap();
+        game.play(&Move::MoveTo(1, 3)).unwrap();
+        game.play(&Move::MoveTo(2, 3)).unwrap();
+        game.play(&Move::MoveTo(1, 4)).unwrap();
+        assert_eq!(Some(Player::Player1), game.winner());
+        assert!(game.legal_moves().is_empty());
+
+        while game.undo().is_some() {}
+        assert_eq!(game.start(), game.board());
+        assert_eq!(Player::Player1, game.current_player());
+    }
+}
@@ -0,0 +1,349 @@
+//! Serde support for boards and squares.
+//!
+//! Human readable formats such as JSON get a versioned object using the
+//! square notation from [`crate::notation`]:
+//!
+//! ```text
+//! {"version":1,"size":9,"horizontal":["e3"],"vertical":[],
+//!  "pawns":["e2","e8"],"walls_left":[9,10],"to_move":"Player2"}
+//! ```
+//!
+//! with pawns and walls left by player number (player 1, 2, 3, 4), not in
+//! the order the players take turns. Binary formats get the same
+//! fields as a tuple, with squares as indices `y * size + x` and walls as
+//! indices `y * (size - 1) + x` of their joints. Both are checked like a
+//! position string when read.
+
+use crate::{
+    any::AnyBoard,
+    bitpacked::{BoardV2, Position},
+    fen,
+    fen::FenError,
+    is_valid_size,
+    v1::BoardV1,
+    Board, Move, Orientation, Player,
+};
+use alloc::{format, string::String, vec::Vec};
+use core::convert::TryFrom;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+const VERSION: u8 = 1;
+
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let (x, y) = (*self).into();
+            serializer.collect_str(&Move::MoveTo(x, y))
+        } else {
+            serializer.serialize_u8(self.idx())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let square = String::deserialize(deserializer)?;
+            match square.parse() {
+                Ok(Move::MoveTo(x, y)) => Position::try_from((x, y)).ok(),
+                _ => None,
+            }
+            .ok_or_else(|| de::Error::custom(format!("can't read square {:?}", square)))
+        } else {
+            let idx = u8::deserialize(deserializer)?;
+            Position::new(idx)
+                .ok_or_else(|| de::Error::custom(format!("square {} is off the board", idx)))
+        }
+    }
+}
+
+/// A square, or the joint of a wall, on a board of any size.
+struct Square(u8, u8);
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&Move::MoveTo(self.0, self.1))
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let square = String::deserialize(deserializer)?;
+        match square.parse() {
+            Ok(Move::MoveTo(x, y)) => Ok(Square(x, y)),
+            _ => Err(de::Error::custom(format!("can't read square {:?}", square))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Readable {
+    version: u8,
+    size: u8,
+    horizontal: Vec<Square>,
+    vertical: Vec<Square>,
+    pawns: Vec<Square>,
+    walls_left: Vec<u8>,
+    to_move: Player,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Compact(u8, u8, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Player);
+
+/// A board taken apart into what both formats store.
+struct Parts {
+    size: u8,
+    horizontal: Vec<(u8, u8)>,
+    vertical: Vec<(u8, u8)>,
+    pawns: Vec<(u8, u8)>,
+    walls_left: Vec<u8>,
+    to_move: Player,
+}
+
+impl Parts {
+    fn of<B: Board>(board: &B, to_move: Player) -> Self {
+        let size = board.size();
+        let walls = |orientation| {
+            (0..size - 1)
+                .flat_map(|y| (0..size - 1).map(move |x| (x, y)))
+                .filter(|location| board.get_wall_state(*location) == Some(orientation))
+                .collect()
+        };
+        let players = &Player::ALL[..board.num_players() as usize];
+        Self {
+            size,
+            horizontal: walls(Orientation::Horizontal),
+            vertical: walls(Orientation::Vertical),
+            pawns: players.iter().map(|p| board.player_location(*p)).collect(),
+            walls_left: players.iter().map(|p| board.available_walls(*p)).collect(),
+            to_move,
+        }
+    }
+
+    fn build<B: Board, E: de::Error>(self) -> Result<B, E> {
+        fen::build(
+            self.size,
+            &self.horizontal,
+            &self.vertical,
+            &self.pawns,
+            &self.walls_left,
+            self.to_move,
+        )
+        .map_err(de::Error::custom)
+    }
+
+    fn serialize<S: Serializer>(self, serializer: S) -> Result<S::Ok, S::Error> {
+        let squares = |squares: Vec<(u8, u8)>| {
+            squares
+                .into_iter()
+                .map(|(x, y)| Square(x, y))
+                .collect::<Vec<_>>()
+        };
+        if serializer.is_human_readable() {
+            Readable {
+                version: VERSION,
+                size: self.size,
+                horizontal: squares(self.horizontal),
+                vertical: squares(self.vertical),
+                pawns: squares(self.pawns),
+                walls_left: self.walls_left,
+                to_move: self.to_move,
+            }
+            .serialize(serializer)
+        } else {
+            let indices = |squares: &[(u8, u8)], width: u8| {
+                squares.iter().map(|(x, y)| y * width + x).collect()
+            };
+            let walls = self.size - 1;
+            Compact(
+                VERSION,
+                self.size,
+                indices(&self.horizontal, walls),
+                indices(&self.vertical, walls),
+                indices(&self.pawns, self.size),
+                self.walls_left,
+                self.to_move,
+            )
+            .serialize(serializer)
+        }
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let check_version = |version| {
+            if version == VERSION {
+                Ok(())
+            } else {
+                Err(de::Error::custom(format!(
+                    "unsupported board version {}",
+                    version
+                )))
+            }
+        };
+        let squares =
+            |squares: Vec<Square>| squares.into_iter().map(|Square(x, y)| (x, y)).collect();
+        if deserializer.is_human_readable() {
+            let readable = Readable::deserialize(deserializer)?;
+            check_version(readable.version)?;
+            Ok(Self {
+                size: readable.size,
+                horizontal: squares(readable.horizontal),
+                vertical: squares(readable.vertical),
+                pawns: squares(readable.pawns),
+                walls_left: readable.walls_left,
+                to_move: readable.to_move,
+            })
+        } else {
+            let Compact(version, size, horizontal, vertical, pawns, walls_left, to_move) =
+                Compact::deserialize(deserializer)?;
+            check_version(version)?;
+            if !is_valid_size(size) {
+                return Err(de::Error::custom(FenError::BadSize));
+            }
+            // squares past the last row end up off the board, which `build`
+            // catches
+            let from_indices = |indices: Vec<u8>, width: u8| {
+                indices
+                    .into_iter()
+                    .map(|i| (i % width, i / width))
+                    .collect()
+            };
+            Ok(Self {
+                size,
+                horizontal: from_indices(horizontal, size - 1),
+                vertical: from_indices(vertical, size - 1),
+                pawns: from_indices(pawns, size),
+                walls_left,
+                to_move,
+            })
+        }
+    }
+}
+
+impl Serialize for BoardV2 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Parts::of(self, self.side_to_move()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BoardV2 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Parts::deserialize(deserializer)?.build()
+    }
+}
+
+/// `BoardV1` doesn't track the side to move, so it writes player 1 and
+/// ignores the side when read.
+impl Serialize for BoardV1 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Parts::of(self, Player::Player1).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BoardV1 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Parts::deserialize(deserializer)?.build()
+    }
+}
+
+/// Writes whichever board it holds, and reads back into `BoardV2` when the
+/// board fits.
+impl Serialize for AnyBoard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AnyBoard::V2(board) => board.serialize(serializer),
+            AnyBoard::V1(board) => board.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AnyBoard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Parts::deserialize(deserializer)?.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameRecord;
+    use serde::de::DeserializeOwned;
+
+    fn round_trip<B: Serialize + DeserializeOwned + PartialEq>(board: &B) {
+        let json = serde_json::to_string(board).unwrap();
+        assert!(board == &serde_json::from_str::<B>(&json).unwrap());
+        let cbor = serde_cbor::to_vec(board).unwrap();
+        assert!(cbor.len() < json.len());
+        assert!(board == &serde_cbor::from_slice::<B>(&cbor).unwrap());
+    }
+
+    fn round_trip_both(fen: &str) {
+        let game = GameRecord::<BoardV2>::from_fen(fen).unwrap();
+        round_trip(game.board());
+        round_trip(&BoardV1::from(game.board().clone()));
+        round_trip(&AnyBoard::from(game.board().clone()));
+    }
+
+    #[test]
+    fn round_trips_boards() {
+        round_trip_both("- - e1,e9 10,10 1");
+        round_trip_both("e3,d7 a1 e2,e8 9,8 2");
+        round_trip_both("b2 - a3,c1,c5,e3 1,2,0,1 4 5");
+        round_trip_both("a4,c4,e4,g4 - e1,e9 6,6 1");
+
+        let game = GameRecord::<BoardV1>::from_fen("j10,a1 a10 f2,k11 8,9 1 11").unwrap();
+        round_trip(game.board());
+        round_trip(&AnyBoard::from(game.board().clone()));
+        round_trip(&BoardV1::with_size(11).unwrap());
+        let json = serde_json::to_value(game.board()).unwrap();
+        assert_eq!(json["horizontal"], serde_json::json!(["a1", "j10"]));
+        assert_eq!(json["pawns"], serde_json::json!(["f2", "k11"]));
+    }
+
+    #[test]
+    fn writes_readable_json() {
+        let game = GameRecord::<BoardV2>::from_fen("e3 - e2,e8 9,10 2").unwrap();
+        assert_eq!(
+            r#"{"version":1,"size":9,"horizontal":["e3"],"vertical":[],"pawns":["e2","e8"],"walls_left":[9,10],"to_move":"Player2"}"#,
+            serde_json::to_string(game.board()).unwrap()
+        );
+        let board = BoardV2::with_players(5, 4).unwrap();
+        let json = serde_json::to_value(&board).unwrap();
+        assert_eq!(json["pawns"], serde_json::json!(["c1", "c5", "e3", "a3"]));
+        assert_eq!(board, serde_json::from_value(json).unwrap());
+
+        let position: Position = serde_json::from_str(r#""i9""#).unwrap();
+        assert_eq!((8, 8), position.into());
+        assert_eq!(vec![0x18, 80], serde_cbor::to_vec(&position).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_boards() {
+        let read = |json: &str| {
+            serde_json::from_str::<BoardV2>(json)
+                .unwrap_err()
+                .to_string()
+        };
+        let board = r#"{"version":1,"size":9,"horizontal":["e3"],"vertical":["e3"],"pawns":["e2","e8"],"walls_left":[9,9],"to_move":"Player1"}"#;
+        assert_eq!("wall e3v: the wall overlaps an existing wall", read(board));
+        assert!(read(&board.replace(r#""version":1"#, r#""version":2"#))
+            .starts_with("unsupported board version 2"));
+        assert!(read(&board.replace(r#"["e2","e8"]"#, r#"["e2","8e"]"#))
+            .starts_with("can't read square \"8e\""));
+        assert!(read(&board.replace(r#"["e2","e8"]"#, r#"["e2","x8"]"#))
+            .starts_with("pawn on x8 is off the board"));
+        assert!(read(&board.replace("Player1", "Player3"))
+            .starts_with("\"3\" is not a player in this game"));
+
+        let no_size = Compact(
+            VERSION,
+            0,
+            vec![],
+            vec![],
+            vec![0, 1],
+            vec![0, 0],
+            Player::Player1,
+        );
+        let cbor = serde_cbor::to_vec(&no_size).unwrap();
+        assert!(serde_cbor::from_slice::<BoardV2>(&cbor).is_err());
+    }
+}
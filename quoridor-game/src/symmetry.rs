@@ -0,0 +1,146 @@
+//! The symmetries of the board: mirroring left to right, flipping top to
+//! bottom (which swaps players 1 and 2), and both at once. Positions related
+//! by a symmetry play out the same way, so searches and opening books can
+//! store one of them.
+
+use crate::{Move, Player};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Symmetry {
+    Identity,
+    /// Left to right, swapping players 3 and 4.
+    Mirror,
+    /// Top to bottom, swapping players 1 and 2.
+    Flip,
+    /// Mirror and flip together, a half turn.
+    Rotate,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 4] = [
+        Symmetry::Identity,
+        Symmetry::Mirror,
+        Symmetry::Flip,
+        Symmetry::Rotate,
+    ];
+
+    /// The symmetries that keep the turn order of a game with `players`
+    /// players. Mirroring or flipping a four player game reverses the order
+    /// players move in, so only the half turn is left.
+    pub fn for_players(players: u8) -> &'static [Symmetry] {
+        if players == 4 {
+            &[Symmetry::Identity, Symmetry::Rotate]
+        } else {
+            &Symmetry::ALL
+        }
+    }
+
+    pub fn mirrors(self) -> bool {
+        matches!(self, Symmetry::Mirror | Symmetry::Rotate)
+    }
+
+    pub fn flips(self) -> bool {
+        matches!(self, Symmetry::Flip | Symmetry::Rotate)
+    }
+
+    /// Every symmetry undoes itself.
+    pub fn inverse(self) -> Symmetry {
+        self
+    }
+
+    /// The player who takes over `player`'s pawn, walls and goal.
+    pub fn player(self, player: Player) -> Player {
+        match (player, self.flips(), self.mirrors()) {
+            (Player::Player1, true, _) => Player::Player2,
+            (Player::Player2, true, _) => Player::Player1,
+            (Player::Player3, _, true) => Player::Player4,
+            (Player::Player4, _, true) => Player::Player3,
+            (player, _, _) => player,
+        }
+    }
+
+    /// Where `square` ends up on a board of `size`.
+    pub fn square(self, (x, y): (u8, u8), size: u8) -> (u8, u8) {
+        self.point((x, y), size - 1)
+    }
+
+    /// Where a wall at `location` ends up. Wall locations run one short of
+    /// the squares in each direction.
+    pub fn wall(self, location: (u8, u8), size: u8) -> (u8, u8) {
+        self.point(location, size - 2)
+    }
+
+    fn point(self, (x, y): (u8, u8), last: u8) -> (u8, u8) {
+        (
+            if self.mirrors() { last - x } else { x },
+            if self.flips() { last - y } else { y },
+        )
+    }
+}
+
+impl Move {
+    /// The same move seen through `symmetry` on a board of `size`.
+    pub fn transformed(&self, symmetry: Symmetry, size: u8) -> Move {
+        match self {
+            Move::MoveTo(x, y) => {
+                let (x, y) = symmetry.square((*x, *y), size);
+                Move::MoveTo(x, y)
+            }
+            Move::AddWall {
+                location,
+                orientation,
+            } => Move::AddWall {
+                location: symmetry.wall(*location, size),
+                orientation: *orientation,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitpacked::BoardV2, Board, GameRecord};
+
+    #[test]
+    fn transforms_moves() {
+        let wall = "c2h".parse::<Move>().unwrap();
+        assert_eq!("f2h", wall.transformed(Symmetry::Mirror, 9).to_string());
+        assert_eq!("c7h", wall.transformed(Symmetry::Flip, 9).to_string());
+        assert_eq!("f7h", wall.transformed(Symmetry::Rotate, 9).to_string());
+        assert_eq!("b2h", wall.transformed(Symmetry::Mirror, 5).to_string());
+        let step = "b2".parse::<Move>().unwrap();
+        assert_eq!("d4", step.transformed(Symmetry::Rotate, 5).to_string());
+        for symmetry in Symmetry::ALL.iter() {
+            assert_eq!(
+                wall,
+                wall.transformed(*symmetry, 9)
+                    .transformed(symmetry.inverse(), 9)
+            );
+        }
+    }
+
+    #[test]
+    fn transformed_games_agree() {
+        for &(size, players) in &[(9, 2), (7, 2), (5, 2), (9, 4), (7, 4)] {
+            let mut game = GameRecord::new(BoardV2::with_players(size, players).unwrap());
+            for choice in &[3, 0, 70, 1, 41, 2, 9, 100] {
+                let moves = game.legal_moves();
+                game.play(&moves[choice % moves.len()]).unwrap();
+            }
+
+            for symmetry in Symmetry::for_players(players) {
+                let board = game.start().transformed(*symmetry);
+                let mut seen =
+                    GameRecord::from_position(board, symmetry.player(game.start_player()));
+                for applied in game.history() {
+                    let mov = applied.mov.transformed(*symmetry, size);
+                    seen.play(&mov).unwrap();
+                }
+                assert_eq!(seen.board(), &game.board().transformed(*symmetry));
+                assert_eq!(game.board(), &seen.board().transformed(symmetry.inverse()));
+                assert_eq!(game.board().canonical().0, seen.board().canonical().0);
+            }
+        }
+    }
+}
@@ -1,12 +1,12 @@
 use super::*;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Cell<WS> {
     pub right: WS,
     pub bottom: WS,
     pub joint: WS,
 }
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum WallState {
     Wall,
     Open,
@@ -15,48 +15,49 @@ pub enum WallState {
 pub type RegularCell = Cell<WallState>;
 
 impl Board for BoardV1 {
-    fn empty() -> Self {
+    fn with_players(size: u8, players: u8) -> Option<Self> {
+        if !is_valid_size(size) || !is_valid_player_count(players) {
+            return None;
+        }
+
         let open_cell = RegularCell {
             right: WallState::Open,
             bottom: WallState::Open,
             joint: WallState::Open,
         };
 
-        let open_cells = [
-            open_cell, open_cell, open_cell, open_cell, open_cell, open_cell, open_cell, open_cell,
-            open_cell,
-        ];
-
-        BoardV1 {
-            cells: [
-                open_cells, open_cells, open_cells, open_cells, open_cells, open_cells, open_cells,
-                open_cells, open_cells,
+        Some(BoardV1 {
+            cells: [[open_cell; MAX_SIZE as usize]; MAX_SIZE as usize],
+            size,
+            players,
+            locations: [
+                Player::Player1.start(size),
+                Player::Player2.start(size),
+                Player::Player3.start(size),
+                Player::Player4.start(size),
             ],
-            player1_loc: (4, 0),
-            player2_loc: (4, 8),
-            player1_walls: 10,
-            player2_walls: 10,
-        }
+            walls: [walls_per_player(size, players); 4],
+        })
+    }
+
+    fn size(&self) -> u8 {
+        self.size
+    }
+
+    fn num_players(&self) -> u8 {
+        self.players
     }
 
     fn available_walls(&self, player: Player) -> u8 {
-        match player {
-            Player::Player1 => self.player1_walls,
-            Player::Player2 => self.player2_walls,
-        }
+        self.walls[player.index()]
+    }
+
+    fn set_available_walls(&mut self, player: Player, walls: u8) {
+        self.walls[player.index()] = walls;
     }
 
     fn is_passible(&self, (x, y): (u8, u8), (nx, ny): (u8, u8)) -> bool {
-        if nx > 8 {
-            return false;
-        }
-        if ny > 8 {
-            return false;
-        }
-        if x > 8 {
-            return false;
-        }
-        if y > 8 {
+        if nx >= self.size || ny >= self.size || x >= self.size || y >= self.size {
             return false;
         }
 
@@ -69,8 +70,21 @@ impl Board for BoardV1 {
         }
     }
 
-    fn get_wall_state(&self, _location: (u8, u8)) -> Option<Orientation> {
-        todo!()
+    fn get_wall_state(&self, (x, y): (u8, u8)) -> Option<Orientation> {
+        if x >= self.size - 1 || y >= self.size - 1 || self.cell(&(x, y)).joint == WallState::Open {
+            return None;
+        }
+        // Horizontal walls can't overlap, so a run of closed bottoms along a
+        // row is made of walls starting at every other cell.
+        let run = (0..x)
+            .rev()
+            .take_while(|x| self.cell(&(*x, y)).bottom == WallState::Wall)
+            .count();
+        if self.cell(&(x, y)).bottom == WallState::Wall && run % 2 == 0 {
+            Some(Orientation::Horizontal)
+        } else {
+            Some(Orientation::Vertical)
+        }
     }
 
     fn player_location(&self, player: Player) -> (u8, u8) {
@@ -78,7 +92,29 @@ impl Board for BoardV1 {
         (loc.0, loc.1)
     }
 
-    fn move_token(&mut self, player: Player, new_location: (u8, u8)) -> Result<(), ()> {
+    fn remove_wall(&mut self, player: Player, location: (u8, u8), orientation: Orientation) {
+        if location.0 >= self.size - 1 || location.1 >= self.size - 1 {
+            return;
+        }
+        self.walls[player.index()] += 1;
+
+        self.cell_mut(&location).joint = WallState::Open;
+        match orientation {
+            Orientation::Horizontal => {
+                self.cell_mut(&location).bottom = WallState::Open;
+                self.cell_mut(&(location.0 + 1, location.1)).bottom = WallState::Open;
+            }
+            Orientation::Vertical => {
+                self.cell_mut(&location).right = WallState::Open;
+                self.cell_mut(&(location.0, location.1 + 1)).right = WallState::Open;
+            }
+        }
+    }
+
+    fn move_token(&mut self, player: Player, new_location: (u8, u8)) -> Result<(), IllegalMove> {
+        if new_location.0 >= self.size || new_location.1 >= self.size {
+            return Err(IllegalMove::OutOfBounds);
+        }
         *self.location_mut(&player) = new_location;
         Ok(())
     }
@@ -88,11 +124,18 @@ impl Board for BoardV1 {
         player: Player,
         location: (u8, u8),
         orientation: Orientation,
-    ) -> Result<(), ()> {
-        match player {
-            Player::Player1 => self.player1_walls -= 1,
-            Player::Player2 => self.player2_walls -= 1,
+    ) -> Result<(), IllegalMove> {
+        if location.0 >= self.size - 1 || location.1 >= self.size - 1 {
+            return Err(IllegalMove::OutOfBounds);
+        }
+        if self.available_walls(player) == 0 {
+            return Err(IllegalMove::NoWallsLeft);
         }
+        if !self.wall_fits(location, orientation) {
+            return Err(IllegalMove::WallOverlaps);
+        }
+
+        self.walls[player.index()] -= 1;
 
         match orientation {
             Orientation::Horizontal => {
@@ -109,60 +152,62 @@ impl Board for BoardV1 {
         Ok(())
     }
 
-    fn is_legal(&self, player: Player, candidate_move: &Move) -> bool {
+    fn check_move(&self, player: Player, candidate_move: &Move) -> Result<(), IllegalMove> {
         match candidate_move {
             Move::AddWall {
                 location,
                 orientation,
             } => {
-                if match player {
-                    Player::Player1 => self.player1_walls,
-                    Player::Player2 => self.player2_walls,
-                } == 0
-                {
-                    return false;
+                if location.0 >= self.size - 1 || location.1 >= self.size - 1 {
+                    return Err(IllegalMove::OutOfBounds);
+                }
+                if self.available_walls(player) == 0 {
+                    return Err(IllegalMove::NoWallsLeft);
+                }
+                if !self.wall_fits(*location, *orientation) {
+                    return Err(IllegalMove::WallOverlaps);
                 }
-
-                let (x, y) = location;
-                let unfilled = self.cell(&location).joint == WallState::Open
-                    && match orientation {
-                        Orientation::Horizontal => {
-                            self.cell(&location).bottom == WallState::Open
-                                && self.cell(&(x + 1, *y)).bottom == WallState::Open
-                        }
-                        Orientation::Vertical => {
-                            self.cell(&location).right == WallState::Open
-                                && self.cell(&(*x, y + 1)).right == WallState::Open
-                        }
-                    };
 
                 let mut hypo = self.clone();
-                if hypo.add_wall(player, *location, *orientation).is_err() {
-                    return false;
-                }
+                hypo.add_wall(player, *location, *orientation)?;
 
-                unfilled
-                    && hypo.distance_to_goal(Player::Player1).is_some()
-                    && hypo.distance_to_goal(Player::Player2).is_some()
+                if hypo.everyone_can_reach_goal() {
+                    Ok(())
+                } else {
+                    Err(IllegalMove::WallBlocksPath)
+                }
             }
             Move::MoveTo(nx, ny) => {
-                let (x, y) = self.location(&player);
-                self.is_passible((*x, *y), (*nx, *ny))
+                if *nx >= self.size || *ny >= self.size {
+                    return Err(IllegalMove::OutOfBounds);
+                }
+                if self.pawn_moves(player).contains(&(*nx, *ny)) {
+                    Ok(())
+                } else {
+                    Err(IllegalMove::IllegalPawnMove)
+                }
             }
         }
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct BoardV1 {
-    pub cells: [[RegularCell; 9]; 9],
-    pub player1_loc: (u8, u8),
-    pub player2_loc: (u8, u8),
-    pub player1_walls: u8,
-    pub player2_walls: u8,
+    pub cells: [[RegularCell; MAX_SIZE as usize]; MAX_SIZE as usize],
+    pub size: u8,
+    pub players: u8,
+    pub locations: [(u8, u8); 4],
+    pub walls: [u8; 4],
 }
 
 impl BoardV1 {
+    pub fn rows(&self) -> impl Iterator<Item = &[RegularCell]> {
+        self.cells
+            .iter()
+            .take(self.size as usize)
+            .map(move |row| &row[..self.size as usize])
+    }
+
     pub fn cell(&self, (x, y): &(u8, u8)) -> &RegularCell {
         &self.cells[*y as usize][*x as usize]
     }
@@ -172,16 +217,26 @@ impl BoardV1 {
     }
 
     pub fn location(&self, player: &Player) -> &(u8, u8) {
-        match player {
-            Player::Player1 => &self.player1_loc,
-            Player::Player2 => &self.player2_loc,
-        }
+        &self.locations[player.index()]
     }
 
     pub fn location_mut(&mut self, player: &Player) -> &mut (u8, u8) {
-        match player {
-            Player::Player1 => &mut self.player1_loc,
-            Player::Player2 => &mut self.player2_loc,
-        }
+        &mut self.locations[player.index()]
+    }
+
+    /// Whether a wall at `location` would cross or overlap no other wall.
+    fn wall_fits(&self, location: (u8, u8), orientation: Orientation) -> bool {
+        let (x, y) = location;
+        self.cell(&location).joint == WallState::Open
+            && match orientation {
+                Orientation::Horizontal => {
+                    self.cell(&location).bottom == WallState::Open
+                        && self.cell(&(x + 1, y)).bottom == WallState::Open
+                }
+                Orientation::Vertical => {
+                    self.cell(&location).right == WallState::Open
+                        && self.cell(&(x, y + 1)).right == WallState::Open
+                }
+            }
     }
 }
@@ -1,23 +1,42 @@
 use pyo3::prelude::*;
 
-use quoridor_game::{bitpacked::BoardV2, Board, Move, Player};
+use quoridor_ai::{
+    greedy::GreedyAiPlayer,
+    mcts::{MctsAiPlayer, ParseConfigError},
+    rubot::RubotAiPlayer,
+    Agent, Budget,
+};
+use quoridor_game::{any::AnyBoard, bitpacked::BoardV2, Board, GameRecord, Move, Player};
+use std::time::Duration;
 #[pyclass]
 #[derive(Clone)]
 pub struct Game {
-    board: BoardV2,
-    current_player: Player,
+    game: GameRecord<AnyBoard>,
     swapped: bool,
 }
 
 #[pymethods]
 impl Game {
     #[new]
-    pub fn new() -> Game {
-        Game {
-            board: BoardV2::empty(),
-            current_player: Player::Player1,
+    #[args(size = "quoridor_game::DEFAULT_SIZE", players = "2")]
+    pub fn new(size: u8, players: u8) -> PyResult<Game> {
+        Ok(Game {
+            game: GameRecord::new(AnyBoard::with_players(size, players).ok_or_else(|| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "unsupported board size {} for {} players",
+                    size, players
+                ))
+            })?),
             swapped: false,
-        }
+        })
+    }
+
+    pub fn size(&self) -> u8 {
+        self.game.board().size()
+    }
+
+    pub fn num_players(&self) -> u8 {
+        self.game.board().num_players()
     }
 
     pub fn swap_players(&mut self) {
@@ -28,13 +47,14 @@ impl Game {
         std::clone::Clone::clone(self)
     }
 
+    /// Boards above 9x9 don't fit the packed form, so they are written as a
+    /// position string instead.
     pub fn as_str(&self) -> String {
-        format!(
-            "{} {} {}",
-            self.swapped,
-            self.current_player,
-            self.board.repr_string()
-        )
+        let repr = match self.game.board() {
+            AnyBoard::V2(board) => board.repr_string(),
+            AnyBoard::V1(_) => self.game.fen(),
+        };
+        format!("{} {} {}", self.swapped, self.game.current_player(), repr)
     }
 
     #[staticmethod]
@@ -42,13 +62,38 @@ impl Game {
         let (swapped, repr) = repr.split_once(' ')?;
         let (current_player, repr) = repr.split_once(' ')?;
 
+        let board = match BoardV2::from_repr_string(repr) {
+            Some(board) => AnyBoard::V2(board),
+            None => GameRecord::<AnyBoard>::from_fen(repr).ok()?.board().clone(),
+        };
         Some(Game {
             swapped: swapped.parse().ok()?,
-            current_player: current_player.parse().ok()?,
-            board: BoardV2::from_repr_string(repr)?,
+            game: GameRecord::from_position(board, current_player.parse().ok()?),
         })
     }
 
+    /// Starts from a position string such as "e3 - e2,e8 9,10 2".
+    #[staticmethod]
+    pub fn from_fen(position: &str) -> PyResult<Game> {
+        Ok(Game {
+            game: GameRecord::from_fen(position)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?,
+            swapped: false,
+        })
+    }
+
+    pub fn fen(&self) -> String {
+        self.game.fen()
+    }
+
+    /// Plays a move in notation such as "e2" or "e3h".
+    pub fn play(&mut self, mov: &str) -> PyResult<()> {
+        let mov = parse_move(mov)?;
+        self.game
+            .play(&mov)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
     pub fn add_wall(&mut self, x: u8, y: u8, orientation: u8) -> bool {
         apply_move(
             self,
@@ -64,104 +109,71 @@ impl Game {
     }
 
     pub fn available_walls(&self, player: u8) -> u8 {
-        self.board.available_walls(self.map_player(player))
+        self.game.board().available_walls(self.map_player(player))
     }
 
     pub fn can_add_wall(&self, x: u8, y: u8, orientation: u8) -> bool {
-        self.board.is_legal(
-            self.current_player,
-            &Move::AddWall {
+        self.check_add_wall(x, y, orientation).is_none()
+    }
+
+    pub fn check_add_wall(&self, x: u8, y: u8, orientation: u8) -> Option<String> {
+        let orientation = match orientation {
+            0 => quoridor_game::Orientation::Horizontal,
+            1 => quoridor_game::Orientation::Vertical,
+            _ => return Some("unknown wall orientation".to_string()),
+        };
+        self.game
+            .check_move(&Move::AddWall {
                 location: (x, y),
-                orientation: match orientation {
-                    0 => quoridor_game::Orientation::Horizontal,
-                    1 => quoridor_game::Orientation::Vertical,
-                    _ => return false,
-                },
-            },
-        )
+                orientation,
+            })
+            .err()
+            .map(|e| e.to_string())
     }
 
     pub fn can_move_to(&self, new_location: (u8, u8)) -> bool {
-        let dirs = [
-            quoridor_game::Direction::Up,
-            quoridor_game::Direction::Down,
-            quoridor_game::Direction::Left,
-            quoridor_game::Direction::Right,
-        ];
-
-        let current_loc = self.board.player_location(self.current_player);
-
-        let direction = dirs
-            .iter()
-            .filter_map(|d| {
-                if let Some(nl) = d.shift(current_loc) {
-                    if nl == new_location {
-                        return Some(d);
-                    }
-                }
-                None
-            })
-            .next();
+        self.check_move_to(new_location).is_none()
+    }
 
-        if let Some(direction) = direction {
-            self.board
-                .is_legal(self.current_player, &Move::MoveToken(*direction))
-        } else {
-            false
-        }
+    pub fn check_move_to(&self, new_location: (u8, u8)) -> Option<String> {
+        self.game
+            .check_move(&Move::MoveTo(new_location.0, new_location.1))
+            .err()
+            .map(|e| e.to_string())
     }
 
     pub fn distance_to_goal(&self, player: u8) -> i8 {
-        self.board
+        self.game
+            .board()
             .distance_to_goal(self.map_player(player))
             .map(|a| a as i8)
             .unwrap_or(-1)
     }
 
     pub fn move_token_to(&mut self, new_location: (u8, u8)) -> bool {
-        let dirs = [
-            quoridor_game::Direction::Up,
-            quoridor_game::Direction::Down,
-            quoridor_game::Direction::Left,
-            quoridor_game::Direction::Right,
-        ];
-
-        let current_loc = self.board.player_location(self.current_player);
-
-        let direction = dirs
-            .iter()
-            .filter_map(|d| {
-                if let Some(nl) = d.shift(current_loc) {
-                    if nl == new_location {
-                        return Some(d);
-                    }
-                }
-                None
-            })
-            .next();
-
-        if let Some(direction) = direction {
-            apply_move(self, Move::MoveToken(*direction))
-        } else {
-            false
-        }
+        apply_move(self, Move::MoveTo(new_location.0, new_location.1))
     }
 
     pub fn move_token(&mut self, direction: u8) -> bool {
-        apply_move(
-            self,
-            Move::MoveToken(match direction {
-                0 => quoridor_game::Direction::Up,
-                1 => quoridor_game::Direction::Down,
-                2 => quoridor_game::Direction::Left,
-                3 => quoridor_game::Direction::Right,
-                _ => return false,
-            }),
-        )
+        let direction = match direction {
+            0 => quoridor_game::Direction::Up,
+            1 => quoridor_game::Direction::Down,
+            2 => quoridor_game::Direction::Left,
+            3 => quoridor_game::Direction::Right,
+            _ => return false,
+        };
+        let board = self.game.board();
+        match direction.shift(
+            board.player_location(self.game.current_player()),
+            board.size(),
+        ) {
+            Some((x, y)) => apply_move(self, Move::MoveTo(x, y)),
+            None => false,
+        }
     }
 
     pub fn current_player(&self) -> u8 {
-        let mut player = self.current_player;
+        let mut player = self.game.current_player();
         if self.swapped {
             player = player.other();
         }
@@ -169,15 +181,17 @@ impl Game {
         match player {
             Player::Player1 => 1,
             Player::Player2 => 2,
+            Player::Player3 => 3,
+            Player::Player4 => 4,
         }
     }
 
     pub fn get_location(&self, player: u8) -> (u8, u8) {
-        self.board.player_location(self.map_player(player))
+        self.game.board().player_location(self.map_player(player))
     }
 
     pub fn get_wall_status(&self, x: u8, y: u8) -> u8 {
-        match self.board.get_wall_state((x, y)) {
+        match self.game.board().get_wall_state((x, y)) {
             None => 0,
             Some(quoridor_game::Orientation::Horizontal) => 1,
             Some(quoridor_game::Orientation::Vertical) => 2,
@@ -197,7 +211,9 @@ impl Game {
             _ => return false,
         };
 
-        self.board.is_passible((x, y), direction)
+        direction
+            .shift((x, y), self.game.board().size())
+            .is_some_and(|nl| self.game.board().is_passible((x, y), nl))
     }
 
     // pub fn canonical_form(&self) -> Game {
@@ -205,7 +221,7 @@ impl Game {
     //         self.clone()
     //     } else {
     //         Game {
-    //             board: self.board.flip(),
+    //             board: self.game.board().flip(),
     //             current_player: Player::Player1,
     //             false
     //         }
@@ -218,6 +234,8 @@ impl Game {
         let player = match player {
             1 => Player::Player1,
             2 => Player::Player2,
+            3 => Player::Player3,
+            4 => Player::Player4,
             _ => todo!(),
         };
 
@@ -230,17 +248,86 @@ impl Game {
 }
 
 fn apply_move(game: &mut Game, mov: Move) -> bool {
-    if game.board.is_legal(game.current_player, &mov) {
-        if game.board.apply_move(&mov, game.current_player).is_ok() {
-            game.current_player = game.current_player.other();
-            return true;
+    game.game.play(&mov).is_ok()
+}
+
+fn parse_move(mov: &str) -> PyResult<Move> {
+    mov.parse()
+        .map_err(|_| pyo3::exceptions::PyValueError::new_err(format!("can't read move {:?}", mov)))
+}
+
+/// One of the bots, keeping its own copy of the game. Moves go in and out in
+/// notation such as "e2" or "e3h".
+#[pyclass(unsendable)]
+pub struct Ai {
+    agent: Box<dyn Agent<AnyBoard>>,
+}
+
+#[pymethods]
+impl Ai {
+    #[staticmethod]
+    pub fn greedy(game: &Game) -> Ai {
+        Ai {
+            agent: Box::new(GreedyAiPlayer::with_game(game.game.clone())),
+        }
+    }
+
+    /// `config` is as for the command line, such as "10000" playouts or
+    /// "2s,threads=2".
+    #[staticmethod]
+    #[args(config = "\"10000\"")]
+    pub fn mcts(game: &Game, config: &str) -> PyResult<Ai> {
+        let config = config.parse().map_err(|e: ParseConfigError| {
+            pyo3::exceptions::PyValueError::new_err(e.to_string())
+        })?;
+        Ok(Ai {
+            agent: Box::new(MctsAiPlayer::with_config(game.game.clone(), config)),
+        })
+    }
+
+    #[staticmethod]
+    #[args(millis = "1000")]
+    pub fn rubot(game: &Game, millis: u64) -> Ai {
+        Ai {
+            agent: Box::new(RubotAiPlayer::new(
+                game.game.clone(),
+                Budget::Time(Duration::from_millis(millis)),
+            )),
         }
     }
-    false
+
+    pub fn new_game(&mut self, game: &Game) {
+        self.agent.new_game(game.game.clone());
+    }
+
+    pub fn observe(&mut self, mov: &str) -> PyResult<()> {
+        self.agent
+            .observe(&parse_move(mov)?)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Picks and plays a move, thinking for `millis` or `nodes` if given.
+    #[args(millis = "None", nodes = "None")]
+    pub fn choose(&mut self, millis: Option<u64>, nodes: Option<u64>) -> PyResult<String> {
+        let budget = match (millis, nodes) {
+            (Some(millis), _) => Budget::Time(Duration::from_millis(millis)),
+            (None, Some(nodes)) => Budget::Nodes(nodes),
+            (None, None) => Budget::Default,
+        };
+        self.agent
+            .choose(budget)
+            .map(|mov| mov.to_string())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    pub fn reset(&mut self) {
+        self.agent.reset();
+    }
 }
 
 #[pymodule]
 fn quoridor_python(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Game>()?;
+    m.add_class::<Ai>()?;
     Ok(())
 }
@@ -1,5 +1,5 @@
-use quoridor_ai::rubot::QuoridorGame;
-use quoridor_game::{bitpacked::BoardV2, Board, Player};
+use quoridor_ai::{greedy::GreedyAiPlayer, rubot::RubotAiPlayer, Agent, Budget};
+use quoridor_game::{any::AnyBoard, Board, GameRecord, Player};
 use wasm_bindgen::prelude::*;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -10,8 +10,7 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 #[wasm_bindgen]
 pub struct Game {
-    board: BoardV2,
-    current_player: Player,
+    game: GameRecord<AnyBoard>,
 }
 
 #[wasm_bindgen]
@@ -40,61 +39,48 @@ pub enum Orientation {
     Vertical,
 }
 
+/// Any of the bots, searching by steps since there's no clock to read.
 #[wasm_bindgen]
-pub enum AiKind {
-    Greedy,
-    Rubot,
-}
-
-#[wasm_bindgen]
-pub struct Ai(AiKind, QuoridorGame<BoardV2>, u32);
+pub struct Ai(Box<dyn Agent<AnyBoard>>);
 
 #[wasm_bindgen]
 impl Ai {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Ai {
-        Ai(AiKind::Greedy, QuoridorGame::new(), 2000)
+        Ai(Box::new(GreedyAiPlayer::with_game(GameRecord::new(
+            AnyBoard::empty(),
+        ))))
     }
 
     pub fn set_greedy(&mut self) {
-        self.0 = AiKind::Greedy;
+        self.0 = Box::new(GreedyAiPlayer::with_game(self.0.game().clone()));
     }
 
     pub fn set_rubot(&mut self, steps: u32) {
-        self.0 = AiKind::Rubot;
-        self.2 = steps;
+        self.0 = Box::new(RubotAiPlayer::new(
+            self.0.game().clone(),
+            Budget::Nodes(steps.into()),
+        ));
+    }
+
+    /// Starts playing `game` from its current position.
+    pub fn new_game(&mut self, game: &Game) {
+        self.0.new_game(game.game.clone());
     }
 
-    pub fn send(&mut self, mov: JsValue) {
+    pub fn send(&mut self, mov: JsValue) -> Result<(), JsValue> {
         let mov = mov.into_serde().unwrap();
-        self.1.apply_move(&mov).unwrap();
-    }
-
-    pub fn receive(&mut self) -> JsValue {
-        JsValue::from_serde(&{
-            let mov = match &mut self.0 {
-                AiKind::Greedy => {
-                    quoridor_ai::greedy::best_move(self.1.board().clone(), self.1.current_player())
-                        .unwrap()
-                }
-                AiKind::Rubot => {
-                    if let Some(mov) = rubot::Bot::new(self.1.current_player())
-                        .select(&self.1, rubot::Steps(self.2))
-                    {
-                        mov
-                    } else {
-                        quoridor_ai::greedy::best_move(
-                            self.1.board().clone(),
-                            self.1.current_player(),
-                        )
-                        .unwrap()
-                    }
-                }
-            };
-            self.1.apply_move(&mov).unwrap();
-            mov
-        })
-        .unwrap()
+        self.0
+            .observe(&mov)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    pub fn receive(&mut self) -> Result<JsValue, JsValue> {
+        let mov = self
+            .0
+            .choose(Budget::Default)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(JsValue::from_serde(&mov).unwrap())
     }
 }
 
@@ -103,29 +89,75 @@ impl Game {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Game {
         Game {
-            board: BoardV2::empty(),
-            current_player: Player::Player1,
+            game: GameRecord::new(AnyBoard::empty()),
         }
     }
 
+    pub fn with_size(size: u8) -> Option<Game> {
+        Game::with_players(size, 2)
+    }
+
+    pub fn with_players(size: u8, players: u8) -> Option<Game> {
+        Some(Game {
+            game: GameRecord::new(AnyBoard::with_players(size, players)?),
+        })
+    }
+
+    pub fn from_fen(position: &str) -> Result<Game, JsValue> {
+        Ok(Game {
+            game: GameRecord::from_fen(position).map_err(|e| JsValue::from_str(&e.to_string()))?,
+        })
+    }
+
+    pub fn fen(&self) -> String {
+        self.game.fen()
+    }
+
+    /// Starts from a board as given by `board`, with its side to move.
+    pub fn from_board(board: JsValue) -> Result<Game, JsValue> {
+        let board: AnyBoard = board
+            .into_serde()
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let side = board.side_to_move();
+        Ok(Game {
+            game: GameRecord::from_position(board, side),
+        })
+    }
+
+    /// The current board as a JSON object.
+    pub fn board(&self) -> JsValue {
+        JsValue::from_serde(self.game.board()).unwrap()
+    }
+
+    pub fn size(&self) -> u8 {
+        self.game.board().size()
+    }
+
+    pub fn num_players(&self) -> u8 {
+        self.game.board().num_players()
+    }
+
     pub fn result(&self) -> Option<u8> {
-        self.board.result().map(|p| match p {
+        self.game.winner().map(|p| match p {
             Player::Player1 => 1,
             Player::Player2 => 2,
+            Player::Player3 => 3,
+            Player::Player4 => 4,
         })
     }
 
     pub fn copy(&self) -> Game {
         Game {
-            board: self.board.clone(),
-            current_player: self.current_player,
+            game: self.game.clone(),
         }
     }
 
     pub fn current_player(&self) -> u8 {
-        match self.current_player {
+        match self.game.current_player() {
             Player::Player1 => 1,
             Player::Player2 => 2,
+            Player::Player3 => 3,
+            Player::Player4 => 4,
         }
     }
 
@@ -133,10 +165,12 @@ impl Game {
         let player = match player {
             1 => Player::Player1,
             2 => Player::Player2,
+            3 => Player::Player3,
+            4 => Player::Player4,
             _ => panic!(),
         };
 
-        let loc = self.board.player_location(player);
+        let loc = self.game.board().player_location(player);
         Location { x: loc.0, y: loc.1 }
     }
 
@@ -144,34 +178,39 @@ impl Game {
         let player = match player {
             1 => Player::Player1,
             2 => Player::Player2,
+            3 => Player::Player3,
+            4 => Player::Player4,
             _ => panic!(),
         };
 
-        self.board.available_walls(player)
+        self.game.board().available_walls(player)
     }
 
-    pub fn apply_move(&mut self, mov: JsValue) -> bool {
+    pub fn is_legal(&self, mov: JsValue) -> bool {
         let mov = mov.into_serde().unwrap();
-        if self.board.is_legal(self.current_player, &mov) {
-            if self.board.apply_move(&mov, self.current_player).is_ok() {
-                self.current_player = self.current_player.other();
-                return true;
-            }
-        }
-        false
+        self.game.check_move(&mov).is_ok()
+    }
+
+    pub fn apply_move(&mut self, mov: JsValue) -> Result<(), JsValue> {
+        let mov = mov.into_serde().unwrap();
+        self.game
+            .play(&mov)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
     pub fn distance_to_goal(&self, player: u8) -> u8 {
         let player = match player {
             1 => Player::Player1,
             2 => Player::Player2,
+            3 => Player::Player3,
+            4 => Player::Player4,
             _ => panic!(),
         };
-        self.board.distance_to_goal(player).unwrap()
+        self.game.board().distance_to_goal(player).unwrap()
     }
 
     pub fn get_wall_status(&self, x: u8, y: u8) -> WallState {
-        match self.board.get_wall_state((x, y)) {
+        match self.game.board().get_wall_state((x, y)) {
             None => WallState::Empty,
             Some(quoridor_game::Orientation::Horizontal) => WallState::Horizontal,
             Some(quoridor_game::Orientation::Vertical) => WallState::Vertical,
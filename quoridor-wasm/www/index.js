@@ -122,13 +122,17 @@ function startgame() {
                 return;
         }
         
-        if (game.apply_move(move)) {
-            history.addBoard(game.copy(), move);
-            myTurn = false;
-            opponent.postMessage(move);
-
-            updateResult();
+        try {
+            game.apply_move(move);
+        } catch (reason) {
+            infoDiv.innerHTML = "Illegal move: " + reason;
+            return;
         }
+        history.addBoard(game.copy(), move);
+        myTurn = false;
+        opponent.postMessage(move);
+
+        updateResult();
     };
 }
 
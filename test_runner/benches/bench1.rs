@@ -1,13 +1,10 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use quoridor_game::{
-    bitpacked::{can_reach_goal_v2, BoardV2},
-    Board, Move, Orientation, Player,
-};
+use quoridor_game::{bitpacked::BoardV2, v1::BoardV1, Board, Move, Orientation, Player};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let mut board = BoardV2::empty();
     c.bench_function("is_passible", |b| {
-        b.iter(|| board.is_passible(black_box((2, 2)), black_box(quoridor_game::Direction::Down)))
+        b.iter(|| board.is_passible(black_box((2, 2)), black_box((2, 3))))
     });
 
     board
@@ -29,28 +26,30 @@ fn criterion_benchmark(c: &mut Criterion) {
     board
         .add_wall(Player::Player2, (6, 7), Orientation::Horizontal)
         .unwrap();
+    board
+        .add_wall(Player::Player1, (2, 6), Orientation::Horizontal)
+        .unwrap();
+    board
+        .add_wall(Player::Player2, (3, 3), Orientation::Vertical)
+        .unwrap();
 
-    c.bench_function("is_legal_2", |b| {
-        b.iter(|| {
-            assert!(can_reach_goal_v2(
-                black_box(&board),
-                black_box(Player::Player2)
-            ));
-        })
+    // V1 still uses the generic A* from `Board`, V2 the bitboard flood fill
+    let v1 = BoardV1::from(board.clone());
+    c.bench_function("distance_to_goal_astar", |b| {
+        b.iter(|| black_box(&v1).distance_to_goal(black_box(Player::Player2)))
+    });
+    c.bench_function("distance_to_goal_flood_fill", |b| {
+        b.iter(|| black_box(&board).distance_to_goal(black_box(Player::Player2)))
+    });
+    c.bench_function("race_margin", |b| {
+        b.iter(|| black_box(&board).race_margin(Player::Player1, Player::Player2))
     });
 
-    c.bench_function("all_moves", |b| {
-        use mcts::GameState;
-        use quoridor_game::ai::mcts::QuoridorState;
-
-        let qstate = QuoridorState::Clean {
-            board: board.clone(),
-            current_player: Player::Player1,
-        };
-
-        b.iter(|| {
-            qstate.available_moves();
-        })
+    c.bench_function("legal_moves_v1", |b| {
+        b.iter(|| black_box(&v1).legal_moves(Player::Player1))
+    });
+    c.bench_function("legal_moves_v2", |b| {
+        b.iter(|| black_box(&board).legal_moves(Player::Player1))
     });
 }
 